@@ -0,0 +1,636 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::chip8::ROM_ADDRESS;
+
+// Assembles a subset of Octo (https://github.com/JohnEarnest/Octo) into a rom for 0x200:
+// labels, :const, :alias, :call, bare numbers or :byte as data bytes, if ... then,
+// if ... begin ... else ... end, loop ... while ... again and the statements for every opcode Cpu runs.
+// When main is not the first label the rom starts with a jump to it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    let mut assembler = Assembler::new(source);
+    assembler.run()?;
+    Ok(assembler.rom)
+}
+
+#[derive(Clone, Copy)]
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+}
+
+// A label operand resolved once the whole source has been read
+struct Patch<'a> {
+    offset: usize,
+    label: Token<'a>,
+    long: bool,
+}
+
+enum Block {
+    // jump is the offset of the jump that still needs its target
+    If {
+        jump: usize,
+        line: usize,
+    },
+    Else {
+        jump: usize,
+        line: usize,
+    },
+    Loop {
+        start: u16,
+        breaks: Vec<usize>,
+        line: usize,
+    },
+}
+
+struct Assembler<'a> {
+    tokens: Vec<Token<'a>>,
+    pos: usize,
+    rom: Vec<u8>,
+    labels: HashMap<&'a str, u16>,
+    consts: HashMap<&'a str, i32>,
+    aliases: HashMap<&'a str, u8>,
+    patches: Vec<Patch<'a>>,
+    blocks: Vec<Block>,
+}
+
+impl<'a> Assembler<'a> {
+    fn new(source: &'a str) -> Self {
+        let mut tokens = Vec::new();
+        for (i, line) in source.lines().enumerate() {
+            let code = line.split('#').next().unwrap_or_default();
+            for text in code.split_whitespace() {
+                tokens.push(Token { text, line: i + 1 });
+            }
+        }
+        Self {
+            tokens,
+            pos: 0,
+            rom: Vec::new(),
+            labels: HashMap::new(),
+            consts: HashMap::new(),
+            aliases: HashMap::new(),
+            patches: Vec::new(),
+            blocks: Vec::new(),
+        }
+    }
+
+    fn run(&mut self) -> Result<(), AsmError> {
+        let main_first =
+            self.tokens.len() >= 2 && self.tokens[0].text == ":" && self.tokens[1].text == "main";
+        let has_main = self
+            .tokens
+            .windows(2)
+            .any(|t| t[0].text == ":" && t[1].text == "main");
+        if has_main && !main_first {
+            let label = Token {
+                text: "main",
+                line: 1,
+            };
+            self.emit_label(0x1000, label);
+        }
+
+        while self.pos < self.tokens.len() {
+            self.statement()?;
+        }
+
+        if let Some(block) = self.blocks.last() {
+            let (line, name) = match *block {
+                Block::If { line, .. } | Block::Else { line, .. } => (line, "if"),
+                Block::Loop { line, .. } => (line, "loop"),
+            };
+            return Err(error(line, format!("{} is missing its end", name)));
+        }
+
+        for patch in &self.patches {
+            let label = patch.label;
+            let addr = *self
+                .labels
+                .get(label.text)
+                .ok_or_else(|| error(label.line, format!("undefined label '{}'", label.text)))?;
+            if patch.long {
+                self.rom[patch.offset..patch.offset + 2].copy_from_slice(&addr.to_be_bytes());
+            } else {
+                check_addr(label, addr)?;
+                self.rom[patch.offset] |= (addr >> 8) as u8;
+                self.rom[patch.offset + 1] = addr as u8;
+            }
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self) -> Result<(), AsmError> {
+        let token = self.next()?;
+        match token.text {
+            ":" => {
+                let name = self.name()?;
+                let here = self.here();
+                if self.labels.insert(name.text, here).is_some() {
+                    return Err(error(
+                        name.line,
+                        format!("label '{}' is defined twice", name.text),
+                    ));
+                }
+            }
+            ":const" => {
+                let name = self.name()?;
+                let value = self.number()?;
+                self.consts.insert(name.text, value);
+            }
+            ":alias" => {
+                let name = self.name()?;
+                let register = self.register()?;
+                self.aliases.insert(name.text, register as u8);
+            }
+            ":call" => self.address(0x2000)?,
+            ":byte" => {
+                let byte = self.byte()?;
+                self.rom.push(byte as u8);
+            }
+            "clear" => self.emit(0x00E0),
+            "return" | ";" => self.emit(0x00EE),
+            "scroll-down" => {
+                let n = self.nibble()?;
+                self.emit(0x00C0 | n);
+            }
+            "scroll-up" => {
+                let n = self.nibble()?;
+                self.emit(0x00D0 | n);
+            }
+            "scroll-right" => self.emit(0x00FB),
+            "scroll-left" => self.emit(0x00FC),
+            "exit" => self.emit(0x00FD),
+            "lores" => self.emit(0x00FE),
+            "hires" => self.emit(0x00FF),
+            "jump" => self.address(0x1000)?,
+            "jump0" => self.address(0xB000)?,
+            "i" => self.i_statement()?,
+            "delay" => {
+                self.expect(":=")?;
+                let x = self.register()?;
+                self.emit(0xF015 | x << 8);
+            }
+            "pitch" => {
+                self.expect(":=")?;
+                let x = self.register()?;
+                self.emit(0xF03A | x << 8);
+            }
+            "audio" => self.emit(0xF002),
+            "buzzer" => {
+                self.expect(":=")?;
+                let x = self.register()?;
+                self.emit(0xF018 | x << 8);
+            }
+            "save" | "load" => {
+                let x = self.register()?;
+                if self.peek() == Some("-") {
+                    self.pos += 1;
+                    let y = self.register()?;
+                    let opcode = if token.text == "save" { 0x5002 } else { 0x5003 };
+                    self.emit(opcode | x << 8 | y << 4);
+                } else {
+                    let opcode = if token.text == "save" { 0xF055 } else { 0xF065 };
+                    self.emit(opcode | x << 8);
+                }
+            }
+            "saveflags" => {
+                let x = self.register()?;
+                self.emit(0xF075 | x << 8);
+            }
+            "loadflags" => {
+                let x = self.register()?;
+                self.emit(0xF085 | x << 8);
+            }
+            "bcd" => {
+                let x = self.register()?;
+                self.emit(0xF033 | x << 8);
+            }
+            "sprite" => {
+                let x = self.register()?;
+                let y = self.register()?;
+                let n = self.nibble()?;
+                self.emit(0xD000 | x << 8 | y << 4 | n);
+            }
+            "plane" => {
+                let n = self.nibble()?;
+                if n > 3 {
+                    return Err(error(token.line, "plane must be 0 to 3".to_string()));
+                }
+                self.emit(0xF001 | n << 8);
+            }
+            "if" => {
+                let skip = self.condition()?;
+                let word = self.next()?;
+                match word.text {
+                    "then" => self.emit(skip),
+                    "begin" => {
+                        self.emit(invert(skip));
+                        let jump = self.rom.len();
+                        self.emit(0x1000);
+                        self.blocks.push(Block::If {
+                            jump,
+                            line: token.line,
+                        });
+                    }
+                    _ => return Err(unexpected(word, "then or begin")),
+                }
+            }
+            "else" => match self.blocks.pop() {
+                Some(Block::If { jump, line }) => {
+                    let end = self.rom.len();
+                    self.emit(0x1000);
+                    self.patch_jump(jump, token)?;
+                    self.blocks.push(Block::Else { jump: end, line });
+                }
+                _ => return Err(error(token.line, "else without if ... begin".to_string())),
+            },
+            "end" => match self.blocks.pop() {
+                Some(Block::If { jump, .. }) | Some(Block::Else { jump, .. }) => {
+                    self.patch_jump(jump, token)?
+                }
+                _ => return Err(error(token.line, "end without if ... begin".to_string())),
+            },
+            "loop" => {
+                let start = self.here();
+                self.blocks.push(Block::Loop {
+                    start,
+                    breaks: Vec::new(),
+                    line: token.line,
+                });
+            }
+            "while" => {
+                // Jumps past again when the condition does not hold
+                let skip = self.condition()?;
+                self.emit(invert(skip));
+                let jump = self.rom.len();
+                self.emit(0x1000);
+                let innermost = self.blocks.iter_mut().rev().find_map(|b| match b {
+                    Block::Loop { breaks, .. } => Some(breaks),
+                    _ => None,
+                });
+                match innermost {
+                    Some(breaks) => breaks.push(jump),
+                    None => return Err(error(token.line, "while outside of a loop".to_string())),
+                }
+            }
+            "again" => match self.blocks.pop() {
+                Some(Block::Loop { start, breaks, .. }) => {
+                    check_addr(token, start)?;
+                    self.emit(0x1000 | start);
+                    for jump in breaks {
+                        self.patch_jump(jump, token)?;
+                    }
+                }
+                _ => return Err(error(token.line, "again without loop".to_string())),
+            },
+            text if self.is_register(text) => self.register_statement(token)?,
+            text => {
+                if let Some(value) = self.value(text) {
+                    let byte = to_byte(token, value)?;
+                    self.rom.push(byte);
+                } else if is_name(text) {
+                    // A bare label calls the subroutine
+                    self.emit_label(0x2000, token);
+                } else {
+                    return Err(unexpected(token, "a statement"));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn i_statement(&mut self) -> Result<(), AsmError> {
+        let op = self.next()?;
+        match op.text {
+            ":=" => match self.peek() {
+                Some("long") => {
+                    self.pos += 1;
+                    self.emit(0xF000);
+                    let target = self.next()?;
+                    match self.value(target.text) {
+                        Some(value) if (0..=0xFFFF).contains(&value) => {
+                            self.rom.extend_from_slice(&(value as u16).to_be_bytes())
+                        }
+                        Some(_) => {
+                            return Err(error(target.line, "address out of range".to_string()))
+                        }
+                        None => {
+                            self.patches.push(Patch {
+                                offset: self.rom.len(),
+                                label: target,
+                                long: true,
+                            });
+                            self.rom.extend_from_slice(&[0, 0]);
+                        }
+                    }
+                }
+                Some("hex") => {
+                    self.pos += 1;
+                    let x = self.register()?;
+                    self.emit(0xF029 | x << 8);
+                }
+                Some("bighex") => {
+                    self.pos += 1;
+                    let x = self.register()?;
+                    self.emit(0xF030 | x << 8);
+                }
+                _ => self.address(0xA000)?,
+            },
+            "+=" => {
+                let x = self.register()?;
+                self.emit(0xF01E | x << 8);
+            }
+            _ => return Err(unexpected(op, ":= or +=")),
+        }
+        Ok(())
+    }
+
+    fn register_statement(&mut self, token: Token<'a>) -> Result<(), AsmError> {
+        let x = self.register_at(token)?;
+        let op = self.next()?;
+        let rhs_register = self.peek_register();
+        let alu = match op.text {
+            ":=" => 0x0,
+            "|=" => 0x1,
+            "&=" => 0x2,
+            "^=" => 0x3,
+            "+=" => 0x4,
+            "-=" => 0x5,
+            ">>=" => 0x6,
+            "=-" => 0x7,
+            "<<=" => 0xE,
+            _ => return Err(unexpected(op, "an assignment")),
+        };
+        if rhs_register {
+            let y = self.register()?;
+            self.emit(0x8000 | x << 8 | y << 4 | alu);
+            return Ok(());
+        }
+
+        match (op.text, self.peek()) {
+            (":=", Some("random")) => {
+                self.pos += 1;
+                let kk = self.byte()?;
+                self.emit(0xC000 | x << 8 | kk);
+            }
+            (":=", Some("delay")) => {
+                self.pos += 1;
+                self.emit(0xF007 | x << 8);
+            }
+            (":=", Some("key")) => {
+                self.pos += 1;
+                self.emit(0xF00A | x << 8);
+            }
+            (":=", _) => {
+                let kk = self.byte()?;
+                self.emit(0x6000 | x << 8 | kk);
+            }
+            ("+=", _) => {
+                let kk = self.byte()?;
+                self.emit(0x7000 | x << 8 | kk);
+            }
+            ("-=", _) => {
+                let kk = self.byte()?;
+                self.emit(0x7000 | x << 8 | (kk as u8).wrapping_neg() as u16);
+            }
+            _ => return Err(unexpected(op, "a register operand")),
+        }
+        Ok(())
+    }
+
+    // Returns the opcode that skips the next instruction when the condition is false
+    fn condition(&mut self) -> Result<u16, AsmError> {
+        let x = self.register()?;
+        let op = self.next()?;
+        match op.text {
+            "==" | "!=" => {
+                let equal = op.text == "==";
+                if self.peek_register() {
+                    let y = self.register()?;
+                    Ok(if equal { 0x9000 } else { 0x5000 } | x << 8 | y << 4)
+                } else {
+                    let kk = self.byte()?;
+                    Ok(if equal { 0x4000 } else { 0x3000 } | x << 8 | kk)
+                }
+            }
+            "key" => Ok(0xE0A1 | x << 8),
+            "-key" => Ok(0xE09E | x << 8),
+            _ => Err(unexpected(op, "==, !=, key or -key")),
+        }
+    }
+
+    fn here(&self) -> u16 {
+        (ROM_ADDRESS + self.rom.len()) as u16
+    }
+
+    fn emit(&mut self, opcode: u16) {
+        self.rom.extend_from_slice(&opcode.to_be_bytes());
+    }
+
+    fn emit_label(&mut self, opcode: u16, label: Token<'a>) {
+        self.patches.push(Patch {
+            offset: self.rom.len(),
+            label,
+            long: false,
+        });
+        self.emit(opcode);
+    }
+
+    // Emits an opcode with a 12 bit address taken from a number, constant or label
+    fn address(&mut self, opcode: u16) -> Result<(), AsmError> {
+        let token = self.next()?;
+        match self.value(token.text) {
+            Some(value) => {
+                let addr = u16::try_from(value)
+                    .map_err(|_| error(token.line, "address out of range".to_string()))?;
+                check_addr(token, addr)?;
+                self.emit(opcode | addr);
+            }
+            None if is_name(token.text) => self.emit_label(opcode, token),
+            None => return Err(unexpected(token, "an address")),
+        }
+        Ok(())
+    }
+
+    fn patch_jump(&mut self, offset: usize, token: Token) -> Result<(), AsmError> {
+        let here = self.here();
+        check_addr(token, here)?;
+        self.rom[offset] |= (here >> 8) as u8;
+        self.rom[offset + 1] = here as u8;
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Token<'a>, AsmError> {
+        let token = self.tokens.get(self.pos).copied().ok_or_else(|| {
+            let line = self.tokens.last().map_or(1, |t| t.line);
+            error(line, "unexpected end of file".to_string())
+        })?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).map(|t| t.text)
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), AsmError> {
+        let token = self.next()?;
+        if token.text != text {
+            return Err(unexpected(token, text));
+        }
+        Ok(())
+    }
+
+    fn name(&mut self) -> Result<Token<'a>, AsmError> {
+        let token = self.next()?;
+        if !is_name(token.text) || self.is_register(token.text) {
+            return Err(unexpected(token, "a name"));
+        }
+        Ok(token)
+    }
+
+    fn peek_register(&self) -> bool {
+        matches!(self.peek(), Some(text) if self.is_register(text))
+    }
+
+    fn is_register(&self, text: &str) -> bool {
+        self.aliases.contains_key(text) || register_number(text).is_some()
+    }
+
+    fn register(&mut self) -> Result<u16, AsmError> {
+        let token = self.next()?;
+        self.register_at(token)
+    }
+
+    fn register_at(&self, token: Token) -> Result<u16, AsmError> {
+        self.aliases
+            .get(token.text)
+            .copied()
+            .or_else(|| register_number(token.text))
+            .map(u16::from)
+            .ok_or_else(|| unexpected(token, "a register"))
+    }
+
+    // A number literal or a constant
+    fn value(&self, text: &str) -> Option<i32> {
+        self.consts
+            .get(text)
+            .copied()
+            .or_else(|| parse_number(text))
+    }
+
+    fn number(&mut self) -> Result<i32, AsmError> {
+        let token = self.next()?;
+        self.value(token.text)
+            .ok_or_else(|| unexpected(token, "a number"))
+    }
+
+    fn byte(&mut self) -> Result<u16, AsmError> {
+        let token = self.next()?;
+        let value = self
+            .value(token.text)
+            .ok_or_else(|| unexpected(token, "a number"))?;
+        Ok(to_byte(token, value)? as u16)
+    }
+
+    fn nibble(&mut self) -> Result<u16, AsmError> {
+        let token = self.next()?;
+        match self.value(token.text) {
+            Some(value) if (0..=15).contains(&value) => Ok(value as u16),
+            Some(_) => Err(error(
+                token.line,
+                format!("{} does not fit in 4 bits", token.text),
+            )),
+            None => Err(unexpected(token, "a number")),
+        }
+    }
+}
+
+// Swaps a skip opcode for the one testing the opposite condition
+fn invert(skip: u16) -> u16 {
+    match skip >> 12 {
+        0x3 => skip + 0x1000,
+        0x4 => skip - 0x1000,
+        0x5 => skip + 0x4000,
+        0x9 => skip - 0x4000,
+        // ExA1 and Ex9E
+        _ => skip ^ 0x003F,
+    }
+}
+
+fn register_number(text: &str) -> Option<u8> {
+    let mut chars = text.chars();
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some('v'), Some(digit), None) | (Some('V'), Some(digit), None) => {
+            digit.to_digit(16).map(|d| d as u8)
+        }
+        _ => None,
+    }
+}
+
+fn parse_number(text: &str) -> Option<i32> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i32::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        i32::from_str_radix(binary, 2).ok()?
+    } else {
+        digits.parse().ok()?
+    };
+    Some(if negative { -value } else { value })
+}
+
+fn is_name(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+}
+
+// Negative numbers down to -128 are stored as two's complement
+fn to_byte(token: Token, value: i32) -> Result<u8, AsmError> {
+    if (-128..=255).contains(&value) {
+        Ok(value as u8)
+    } else {
+        Err(error(
+            token.line,
+            format!("{} does not fit in a byte", token.text),
+        ))
+    }
+}
+
+fn check_addr(token: Token, addr: u16) -> Result<(), AsmError> {
+    if addr > 0xFFF {
+        return Err(error(
+            token.line,
+            format!("address {:#X} does not fit in 12 bits, use i := long", addr),
+        ));
+    }
+    Ok(())
+}
+
+fn error(line: usize, message: String) -> AsmError {
+    AsmError { line, message }
+}
+
+fn unexpected(token: Token, expected: &str) -> AsmError {
+    error(
+        token.line,
+        format!("expected {}, found '{}'", expected, token.text),
+    )
+}
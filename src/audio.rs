@@ -0,0 +1,248 @@
+use std::f32::consts::PI;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Waveform {
+    Square,
+    Triangle,
+    Sawtooth,
+    Sine,
+}
+
+impl Waveform {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "square" => Some(Waveform::Square),
+            "triangle" => Some(Waveform::Triangle),
+            "sawtooth" => Some(Waveform::Sawtooth),
+            "sine" => Some(Waveform::Sine),
+            _ => None,
+        }
+    }
+}
+
+// The tone played while the sound timer is nonzero
+pub struct Beeper {
+    // Hz
+    pub frequency: f32,
+    // 0.0 to 1.0
+    pub volume: f32,
+    pub waveform: Waveform,
+    // Position within the current period, 0.0 to 1.0
+    phase: f32,
+}
+
+impl Beeper {
+    pub fn new() -> Self {
+        Self {
+            frequency: 440.0,
+            volume: 0.25,
+            waveform: Waveform::Square,
+            phase: 0.0,
+        }
+    }
+
+    pub fn next_sample(&mut self, sample_rate: u32) -> f32 {
+        let t = self.phase;
+        let value = match self.waveform {
+            Waveform::Square => {
+                if t < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Triangle => 1.0 - 4.0 * (t - 0.5).abs(),
+            Waveform::Sawtooth => 2.0 * t - 1.0,
+            Waveform::Sine => (2.0 * PI * t).sin(),
+        };
+        self.phase = (self.phase + self.frequency / sample_rate as f32).fract();
+        value * self.volume
+    }
+
+    // Every beep starts at the beginning of a period
+    pub fn reset(&mut self) {
+        self.phase = 0.0;
+    }
+}
+
+impl Default for Beeper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// What plays during one 60th of a second
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tone {
+    Off,
+    Beep,
+    // An XO-CHIP audio pattern and pitch
+    Pattern([u8; 16], u8),
+}
+
+// Bits per second an XO-CHIP pattern is played at for a pitch register value
+pub fn pattern_rate(pitch: u8) -> f32 {
+    4000.0 * 2f32.powf((pitch as f32 - 64.0) / 48.0)
+}
+
+// Receives mono samples between -1.0 and 1.0
+pub trait AudioSink {
+    // Samples per second the sink expects, 0 if it doesn't want any
+    fn sample_rate(&self) -> u32;
+    fn write(&mut self, samples: &[f32]);
+}
+
+// Discards the audio, nothing is generated for it
+pub struct NullSink;
+
+impl AudioSink for NullSink {
+    fn sample_rate(&self) -> u32 {
+        0
+    }
+
+    fn write(&mut self, _samples: &[f32]) {}
+}
+
+// Keeps every sample in memory, for headless runs and tests
+pub struct BufferSink {
+    pub sample_rate: u32,
+    pub samples: Vec<f32>,
+}
+
+impl BufferSink {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            samples: Vec::new(),
+        }
+    }
+}
+
+impl AudioSink for BufferSink {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn write(&mut self, samples: &[f32]) {
+        self.samples.extend_from_slice(samples);
+    }
+}
+
+// Writes 16 bit mono PCM to a WAV file. The first write error is kept and returned by
+// finish, the recording stops at that point.
+pub struct WavSink {
+    sample_rate: u32,
+    writer: hound::WavWriter<BufWriter<File>>,
+    error: Option<hound::Error>,
+}
+
+impl WavSink {
+    pub fn create<P: AsRef<Path>>(path: P, sample_rate: u32) -> Result<Self, hound::Error> {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        Ok(Self {
+            sample_rate,
+            writer: hound::WavWriter::create(path, spec)?,
+            error: None,
+        })
+    }
+
+    // Completes the header, the file isn't valid before this
+    pub fn finish(self) -> Result<(), hound::Error> {
+        match self.error {
+            Some(error) => Err(error),
+            None => self.writer.finalize(),
+        }
+    }
+}
+
+impl AudioSink for WavSink {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn write(&mut self, samples: &[f32]) {
+        if self.error.is_some() {
+            return;
+        }
+        for sample in samples {
+            let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            if let Err(error) = self.writer.write_sample(value) {
+                self.error = Some(error);
+                return;
+            }
+        }
+    }
+}
+
+// Renders the sound timer off the virtual clock, one 60th of a second at every timer tick,
+// so the audio lines up with the emulation whatever the host speed.
+// The samples wait in `samples` until flushed to a sink.
+pub struct Audio {
+    pub beeper: Beeper,
+    // Samples per second, 0 turns audio generation off
+    pub sample_rate: u32,
+    pub samples: Vec<f32>,
+    // Position in the pattern in bits, 0.0 to 128.0
+    pattern_pos: f32,
+}
+
+impl Audio {
+    pub fn new() -> Self {
+        Self {
+            beeper: Beeper::new(),
+            sample_rate: 0,
+            samples: Vec::new(),
+            pattern_pos: 0.0,
+        }
+    }
+
+    // Renders the 60th of a second ending at the tick of this frame.
+    // The length is computed from the frame count so it doesn't drift.
+    pub fn tick(&mut self, frame: u64, tone: Tone) {
+        let rate = self.sample_rate as u64;
+        if rate == 0 {
+            return;
+        }
+        let len = (frame + 1) * rate / 60 - frame * rate / 60;
+        if tone == Tone::Off {
+            self.beeper.reset();
+            self.pattern_pos = 0.0;
+        }
+        for _ in 0..len {
+            let sample = match tone {
+                Tone::Off => 0.0,
+                Tone::Beep => self.beeper.next_sample(self.sample_rate),
+                Tone::Pattern(pattern, pitch) => {
+                    let bit = self.pattern_pos as usize;
+                    let step = pattern_rate(pitch) / self.sample_rate as f32;
+                    self.pattern_pos = (self.pattern_pos + step) % 128.0;
+                    if pattern[bit / 8] & (0x80 >> (bit % 8)) != 0 {
+                        self.beeper.volume
+                    } else {
+                        -self.beeper.volume
+                    }
+                }
+            };
+            self.samples.push(sample);
+        }
+    }
+
+    pub fn flush(&mut self, sink: &mut dyn AudioSink) {
+        sink.write(&self.samples);
+        self.samples.clear();
+    }
+}
+
+impl Default for Audio {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,80 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+
+use crate::audio::AudioSink;
+
+// Plays the audio on the default output device.
+// The emulator produces samples as the virtual clock advances and the device pulls them
+// at its own pace, the queue in between is capped so the latency can't build up.
+pub struct DeviceSink {
+    sample_rate: u32,
+    queue: Arc<Mutex<VecDeque<f32>>>,
+    _stream: cpal::Stream,
+}
+
+impl DeviceSink {
+    pub fn open() -> Result<Self, String> {
+        let device = cpal::default_host()
+            .default_output_device()
+            .ok_or("no audio output device")?;
+        let supported = device.default_output_config().map_err(|e| e.to_string())?;
+        let format = supported.sample_format();
+        let config: cpal::StreamConfig = supported.into();
+        let queue = Arc::new(Mutex::new(VecDeque::new()));
+
+        let stream = match format {
+            cpal::SampleFormat::F32 => build_stream::<f32>(&device, &config, queue.clone()),
+            cpal::SampleFormat::I16 => build_stream::<i16>(&device, &config, queue.clone()),
+            cpal::SampleFormat::U16 => build_stream::<u16>(&device, &config, queue.clone()),
+        }
+        .map_err(|e| e.to_string())?;
+        stream.play().map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            sample_rate: config.sample_rate.0,
+            queue,
+            _stream: stream,
+        })
+    }
+}
+
+impl AudioSink for DeviceSink {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn write(&mut self, samples: &[f32]) {
+        let mut queue = self.queue.lock().unwrap();
+        queue.extend(samples);
+        // Keep at most 100ms queued
+        let max = self.sample_rate as usize / 10;
+        if queue.len() > max {
+            let excess = queue.len() - max;
+            queue.drain(..excess);
+        }
+    }
+}
+
+// Plays the mono samples on every channel, silence when the queue runs dry
+fn build_stream<T: cpal::Sample>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    queue: Arc<Mutex<VecDeque<f32>>>,
+) -> Result<cpal::Stream, cpal::BuildStreamError> {
+    let channels = config.channels as usize;
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+            let mut queue = queue.lock().unwrap();
+            for frame in data.chunks_mut(channels) {
+                let value = cpal::Sample::from::<f32>(&queue.pop_front().unwrap_or(0.0));
+                for sample in frame.iter_mut() {
+                    *sample = value;
+                }
+            }
+        },
+        |e| eprintln!("Audio error: {}", e),
+    )
+}
@@ -9,9 +9,9 @@ pub struct DataBus {
 }
 
 impl DataBus {
-    pub fn new() -> Self {
+    pub fn new(memory_size: usize) -> Self {
         Self {
-            ram: ram::Ram::new(),
+            ram: ram::Ram::new(memory_size),
             display: display::Display::new(),
             keyboard: kb::Keyboard::new(),
         }
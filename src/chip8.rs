@@ -1,52 +1,237 @@
+use super::assembler::{self, AsmError};
+use super::audio::{Audio, Tone};
 use super::bus;
 use super::cpu;
-use std::time::Instant;
+use super::display::{Display, HEIGHT, WIDTH};
+use super::error::Chip8Error;
+use super::kb::Keyboard;
+use super::quirks::Quirks;
+use super::ram::Ram;
+use super::rng::Rng;
+use super::state::{StateError, StateReader, StateWriter};
+
+pub const FONTS: &[u8] = include_bytes!("../FONTS.chip8");
+pub const ROM_ADDRESS: usize = 0x200;
+// Where the cpu starts within the first 60hz frame, 1ms before the first tick. Programs
+// that check the delay timer soon after setting it, like c8_test, see it tick early on.
+pub const DEFAULT_TIMER_PHASE: u64 = 1_000_000 / 60 - 1_000;
 
 pub struct Chip8 {
     pub cpu: cpu::Cpu,
     pub bus: bus::DataBus,
-    pub delay_timer: std::time::Instant,
+    // Virtual time since power on in microseconds, advanced by the execution time of each
+    // instruction. The timers tick off this clock so runs don't depend on the host speed.
+    pub clock: u64,
+    // Number of 60hz timer ticks since power on
+    pub frame: u64,
+    // Number of instructions executed since power on
+    pub instructions: u64,
+    // Sound timer output, off until a sample rate is set
+    pub audio: Audio,
 }
 
 impl Chip8 {
-    pub fn new() -> Self {
+    pub fn new(quirks: Quirks) -> Self {
         Self {
-            cpu: cpu::Cpu::new(),
-            bus: bus::DataBus::new(),
-            delay_timer: std::time::Instant::now(),
+            cpu: cpu::Cpu::new(quirks),
+            bus: bus::DataBus::new(if quirks.xo_chip { 0x10000 } else { 0x1000 }),
+            clock: DEFAULT_TIMER_PHASE,
+            frame: 0,
+            instructions: 0,
+            audio: Audio::new(),
+        }
+    }
+
+    // Loads the built-in fonts and the rom at 0x200
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), Chip8Error> {
+        let capacity = self.bus.ram.mem.len() - ROM_ADDRESS;
+        if rom.len() > capacity {
+            return Err(Chip8Error::RomTooLarge {
+                size: rom.len(),
+                capacity,
+            });
+        }
+        self.load_mem(FONTS, 0x0);
+        self.load_mem(rom, ROM_ADDRESS);
+        Ok(())
+    }
+
+    // Assembles Octo source, see assembler.rs, and loads it like load_rom. A rom too large
+    // for memory is reported at the end of the source.
+    pub fn load_source(&mut self, source: &str) -> Result<(), AsmError> {
+        let rom = assembler::assemble(source)?;
+        self.load_rom(&rom).map_err(|e| AsmError {
+            line: source.lines().count(),
+            message: e.to_string(),
+        })
+    }
+
+    // Where the cpu starts within the first 60hz frame, in microseconds after power on.
+    // The phase between the cpu and the timers is arbitrary on real hardware, see
+    // DEFAULT_TIMER_PHASE.
+    pub fn set_timer_phase(&mut self, micros: u64) {
+        self.clock = self.frame * 1_000_000 / 60 + micros.min(1_000_000 / 60 - 1);
+    }
+
+    // Runs until the next 60hz timer tick
+    pub fn run_frame(&mut self) -> Result<(), Chip8Error> {
+        let frame = self.frame;
+        while self.frame == frame {
+            self.exec_cycle()?;
         }
+        Ok(())
+    }
+
+    // Makes Cxkk produce the same sequence on every run with this seed
+    pub fn seed(&mut self, seed: u64) {
+        self.cpu.rng = Rng::new(seed);
+    }
+
+    // Serializes the whole machine, see state.rs for the layout
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut w = StateWriter::new();
+        w.u64(self.clock);
+        w.u64(self.frame);
+        w.u64(self.instructions);
+        self.cpu.save_state(&mut w);
+        self.bus.ram.save_state(&mut w);
+        self.bus.display.save_state(&mut w);
+        self.bus.keyboard.save_state(&mut w);
+        w.data
     }
 
-    pub fn exec_cycle(&mut self) {
-        if self.cpu.instr_timer.elapsed().as_micros() > self.cpu.instr_time {
-            let instruction = self.fetch_instruction();
-            self.cpu.exec_instruction(&mut self.bus, instruction);
-            self.cpu.instr_timer = Instant::now();
+    // The machine is left untouched if the state can't be read
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        let mut r = StateReader::new(data)?;
+        let clock = r.u64()?;
+        let frame = r.u64()?;
+        let instructions = r.u64()?;
+        let cpu = cpu::Cpu::read_state(&mut r)?;
+        let bus = bus::DataBus {
+            ram: Ram::read_state(&mut r)?,
+            display: Display::read_state(&mut r)?,
+            keyboard: Keyboard::read_state(&mut r)?,
+        };
+        if !r.is_empty() {
+            return Err(StateError::Invalid("length"));
         }
-        self.dec_dt_st();
+        if (bus.ram.mem.len() == 0x10000) != cpu.quirks.xo_chip {
+            return Err(StateError::Invalid("memory size"));
+        }
+        self.cpu = cpu;
+        self.bus = bus;
+        self.clock = clock;
+        self.frame = frame;
+        self.instructions = instructions;
+        Ok(())
+    }
+
+    pub fn set_key(&mut self, key: u8, pressed: bool) {
+        let bit = 1 << (key & 0x0F);
+        let mask = self.bus.keyboard.mask();
+        self.set_keys(if pressed { mask | bit } else { mask & !bit });
+    }
+
+    // Sets every key at once from a bitmask, key 0 in the lowest bit
+    pub fn set_keys(&mut self, mask: u16) {
+        let (pressed, released) = self.bus.keyboard.set_mask(mask);
+        self.resolve_key_wait(pressed, released);
     }
 
-    fn dec_dt_st(&mut self) {
-        // DEC dt and st at 60hz
-        if self.delay_timer.elapsed().as_nanos() > 16666667 {
-            if self.cpu.dt > 0 {
-                self.cpu.dt -= 1;
+    pub fn framebuffer(&self) -> &[[u8; WIDTH]; HEIGHT] {
+        &self.bus.display.framebuffer
+    }
+
+    pub fn sound_active(&self) -> bool {
+        self.cpu.st > 0
+    }
+
+    // Fx0A waits for a key to be pressed and released again, like the COSMAC VIP.
+    // Only presses after the wait started count, a key still held from before doesn't.
+    fn resolve_key_wait(&mut self, pressed: u16, released: u16) {
+        if !self.cpu.wait_for_input {
+            return;
+        }
+        let keyboard = &mut self.bus.keyboard;
+        match keyboard.wait_key {
+            None if pressed != 0 => keyboard.wait_key = Some(pressed.trailing_zeros() as u8),
+            Some(key) if released & (1 << key) != 0 => {
+                self.cpu.reg[keyboard.register as usize] = key;
+                keyboard.wait_key = None;
+                self.cpu.wait_for_input = false;
             }
-            if self.cpu.st > 0 {
-                self.cpu.st -= 1;
+            _ => {}
+        }
+    }
+
+    // Executes the next instruction and advances the clock by its execution time.
+    // While the cpu waits for input, after a draw under the display_wait quirk or after 00FD
+    // the clock skips ahead to the next 60hz tick.
+    pub fn exec_cycle(&mut self) -> Result<(), Chip8Error> {
+        if self.cpu.halted || self.cpu.wait_for_input || self.cpu.wait_for_vblank {
+            self.clock = self.clock.max(self.next_tick());
+        } else {
+            self.exec_next()?;
+            if self.cpu.wait_for_vblank {
+                self.clock = self.next_tick();
+            } else {
+                self.clock += self.cpu.instr_time.max(1);
             }
-            self.delay_timer = Instant::now();
         }
+        while self.clock >= self.next_tick() {
+            self.tick_timers();
+        }
+        Ok(())
+    }
+
+    // Single instruction step of the original library API, the same as exec_cycle
+    pub fn step(&mut self) -> Result<(), Chip8Error> {
+        self.exec_cycle()
+    }
+
+    // Clock time of the next 60hz tick, computed from the frame count so it doesn't drift
+    fn next_tick(&self) -> u64 {
+        (self.frame + 1) * 1_000_000 / 60
+    }
+
+    fn tick_timers(&mut self) {
+        let tone = match self.cpu.audio_pattern {
+            _ if self.cpu.st == 0 => Tone::Off,
+            Some(pattern) => Tone::Pattern(pattern, self.cpu.pitch),
+            None => Tone::Beep,
+        };
+        self.audio.tick(self.frame, tone);
+        if self.cpu.dt > 0 {
+            self.cpu.dt -= 1;
+        }
+        if self.cpu.st > 0 {
+            self.cpu.st -= 1;
+        }
+        self.cpu.wait_for_vblank = false;
+        self.bus.keyboard.end_frame();
+        self.frame += 1;
+    }
+
+    fn exec_next(&mut self) -> Result<(), Chip8Error> {
+        let instruction = self.fetch_instruction()?;
+        self.cpu.exec_instruction(&mut self.bus, instruction)?;
+        self.instructions += 1;
+        Ok(())
     }
 
-    fn fetch_instruction(&self) -> u16 {
-        let byte1: u8 = self.bus.ram.read_byte(self.cpu.pc as usize);
-        let byte2: u8 = self.bus.ram.read_byte(self.cpu.pc as usize + 1);
+    fn fetch_instruction(&self) -> Result<u16, Chip8Error> {
+        let pc = self.cpu.pc;
+        let bytes = self
+            .bus
+            .ram
+            .mem
+            .get(pc as usize..pc as usize + 2)
+            .ok_or(Chip8Error::PcOutOfRange { pc })?;
 
-        (byte1 as u16) << 8 | byte2 as u16
+        Ok((bytes[0] as u16) << 8 | bytes[1] as u16)
     }
 
-    pub fn load_mem(&mut self, rom: &Vec<u8>, offset: usize) {
+    pub fn load_mem(&mut self, rom: &[u8], offset: usize) {
         for (i, value) in rom.iter().enumerate() {
             self.bus.ram.write_byte(offset + i, *value)
         }
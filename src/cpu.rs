@@ -1,7 +1,15 @@
-use rand::Rng;
+use crate::error::{Chip8Error, Fault};
+use crate::instruction::Instruction;
+use crate::quirks::Quirks;
+use crate::rng::Rng;
+use crate::state::{StateError, StateReader, StateWriter};
 
 const START_ADDRESS: u16 = 0x200;
+const BIG_FONT_ADDRESS: u16 = 0x50;
 const VF: usize = 0x0F;
+const STACK_SIZE: usize = 16;
+// Fx3A value for a 4000 bits per second pattern playback (XO-CHIP)
+pub const DEFAULT_PITCH: u8 = 64;
 
 pub struct Cpu {
     pub reg: [u8; 16],
@@ -10,13 +18,23 @@ pub struct Cpu {
     stack: Vec<u16>,
     pub dt: u8,
     pub st: u8,
-    pub instr_time: u128,
-    pub instr_timer: std::time::Instant,
+    // Emulated execution time of the last instruction in microseconds
+    pub instr_time: u64,
     pub wait_for_input: bool,
+    pub wait_for_vblank: bool,
+    pub halted: bool,
+    pub rpl: [u8; 16],
+    // 128 one bit samples played while the sound timer runs, None until F002 loads one (XO-CHIP)
+    pub audio_pattern: Option<[u8; 16]>,
+    // Playback rate of the pattern, see audio::pattern_rate (XO-CHIP)
+    pub pitch: u8,
+    pub quirks: Quirks,
+    // Source for Cxkk, replace it to seed the machine
+    pub rng: Rng,
 }
 
 impl Cpu {
-    pub fn new() -> Self {
+    pub fn new(quirks: Quirks) -> Self {
         Self {
             reg: [0; 16],
             i: 0,
@@ -25,77 +43,160 @@ impl Cpu {
             dt: 0,
             st: 0,
             instr_time: 0,
-            instr_timer: std::time::Instant::now(),
             wait_for_input: false,
+            wait_for_vblank: false,
+            halted: false,
+            rpl: [0; 16],
+            audio_pattern: None,
+            pitch: DEFAULT_PITCH,
+            quirks,
+            rng: Rng::from_entropy(),
         }
     }
 
-    pub fn exec_instruction(&mut self, bus: &mut crate::bus::DataBus, instruction: u16) {
-        let opcode: u8 = ((instruction & 0xF000) >> 12) as u8;
-        let nnn: u16 = instruction & 0x0FFF;
-        let kk: u8 = (instruction & 0x00FF) as u8;
-        let n: u8 = (instruction & 0x000F) as u8;
-        let x: u8 = ((instruction & 0x0F00) >> 8) as u8;
-        let y: u8 = ((instruction & 0x00F0) >> 4) as u8;
-
-        match opcode {
-            0x0 => match kk {
-                0xE0 => self.cls(bus),
-                0xEE => self.ret_s(),
-                _ => panic!("{:#X} Not implemented", instruction),
-            },
-            0x1 => self.jp_addr(nnn),
-            0x2 => self.call_addr(nnn),
-            0x3 => self.se_vx_byte(x, kk),
-            0x4 => self.sne_vx_byte(x, kk),
-            0x5 => self.se_vx_vy(x, y),
-            0x6 => self.ld_vx_byte(x, kk),
-            0x7 => self.add_vx_byte(x, kk),
-            0x8 => match n {
-                0x0 => self.ld_vx_vy(x, y),
-                0x1 => self.or_vx_vy(x, y),
-                0x2 => self.and_vx_vy(x, y),
-                0x3 => self.xor_vx_vy(x, y),
-                0x4 => self.add_vx_vy(x, y),
-                0x5 => self.sub_vx_vy(x, y),
-                0x6 => self.shr_vx(x),
-                0x7 => self.subn_vx_vy(x, y),
-                0xE => self.shl_vx(x),
-                _ => panic!("{:#X} Not implemented", instruction),
-            },
-            0x9 => self.sne_vx_vy(x, y),
-            0xA => self.ld_i_addr(nnn),
-            0xB => self.jmp_v0_addr(nnn),
-            0xC => self.rnd_vx_byte(x, kk),
-            0xD => self.d_xn_n(bus, x, y, n),
-            0xE => match kk {
-                0x9E => self.skp_vx(bus, x),
-                0xA1 => self.sknp_vx(bus, x),
-                _ => panic!("{:#X} Not implemented", instruction),
-            },
-            0xF => match kk {
-                0x07 => self.ld_vx_dt(x),
-                0x0A => self.ld_vx_k(bus, x),
-                0x15 => self.ld_dt_vx(x),
-                0x18 => self.ld_st_vx(x),
-                0x29 => self.ld_f_vx(x),
-                0x1E => self.add_i_vx(x),
-                0x33 => self.ld_b_vx(bus, x),
-                0x55 => self.ld_i_vx(bus, x),
-                0x65 => self.ld_vx_i(bus, x),
-                _ => panic!("{:#X} Not implemented", instruction),
-            },
-            _ => {
-                panic!("{:#X} Not implemented", instruction);
-            }
+    pub fn i(&self) -> u16 {
+        self.i
+    }
+
+    pub fn stack(&self) -> &[u16] {
+        &self.stack
+    }
+
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.bytes(&self.reg);
+        w.u16(self.i);
+        w.u16(self.pc);
+        w.u8(self.stack.len() as u8);
+        for addr in self.stack.iter() {
+            w.u16(*addr);
+        }
+        w.u8(self.dt);
+        w.u8(self.st);
+        w.u64(self.instr_time);
+        w.bool(self.wait_for_input);
+        w.bool(self.wait_for_vblank);
+        w.bool(self.halted);
+        w.bytes(&self.rpl);
+        w.bool(self.audio_pattern.is_some());
+        w.bytes(&self.audio_pattern.unwrap_or([0; 16]));
+        w.u8(self.pitch);
+        self.quirks.save_state(w);
+        w.u64(self.rng.state);
+    }
+
+    pub fn read_state(r: &mut StateReader) -> Result<Self, StateError> {
+        let mut cpu = Self::new(Quirks::default());
+        cpu.reg.copy_from_slice(r.bytes(16)?);
+        cpu.i = r.u16()?;
+        cpu.pc = r.u16()?;
+        let depth = r.u8()? as usize;
+        if depth > STACK_SIZE {
+            return Err(StateError::Invalid("stack"));
+        }
+        for _ in 0..depth {
+            cpu.stack.push(r.u16()?);
+        }
+        cpu.dt = r.u8()?;
+        cpu.st = r.u8()?;
+        cpu.instr_time = r.u64()?;
+        cpu.wait_for_input = r.bool()?;
+        cpu.wait_for_vblank = r.bool()?;
+        cpu.halted = r.bool()?;
+        cpu.rpl.copy_from_slice(r.bytes(16)?);
+        let has_pattern = r.bool()?;
+        let mut pattern = [0; 16];
+        pattern.copy_from_slice(r.bytes(16)?);
+        cpu.audio_pattern = if has_pattern { Some(pattern) } else { None };
+        cpu.pitch = r.u8()?;
+        cpu.quirks = Quirks::read_state(r)?;
+        cpu.rng = Rng::new(r.u64()?);
+        Ok(cpu)
+    }
+
+    pub fn exec_instruction(
+        &mut self,
+        bus: &mut crate::bus::DataBus,
+        instruction: u16,
+    ) -> Result<(), Chip8Error> {
+        let pc = self.pc;
+        let fault = |f: Fault| f.at(pc, instruction);
+        let next = bus
+            .ram
+            .mem
+            .get(pc as usize + 2..pc as usize + 4)
+            .map(|b| (b[0] as u16) << 8 | b[1] as u16);
+        let decoded = Instruction::decode(instruction, next, self.quirks.xo_chip)
+            .ok_or(Chip8Error::UnknownOpcode { pc, instruction })?;
+
+        match decoded {
+            Instruction::ScrollDown(n) => self.scd_n(bus, n),
+            Instruction::ScrollUp(n) => self.scu_n(bus, n),
+            Instruction::Cls => self.cls(bus),
+            Instruction::Ret => self.ret_s().map_err(fault)?,
+            Instruction::ScrollRight => self.scr(bus),
+            Instruction::ScrollLeft => self.scl(bus),
+            Instruction::Exit => self.exit(),
+            Instruction::Lores => self.low(bus),
+            Instruction::Hires => self.high(bus),
+            Instruction::Jp(nnn) => self.jp_addr(nnn),
+            Instruction::Call(nnn) => self.call_addr(nnn).map_err(fault)?,
+            Instruction::SeByte(x, kk) => self.se_vx_byte(bus, x, kk),
+            Instruction::SneByte(x, kk) => self.sne_vx_byte(bus, x, kk),
+            Instruction::SeReg(x, y) => self.se_vx_vy(bus, x, y),
+            Instruction::SaveRange(x, y) => self.ld_i_vx_vy(bus, x, y).map_err(fault)?,
+            Instruction::LoadRange(x, y) => self.ld_vx_vy_i(bus, x, y).map_err(fault)?,
+            Instruction::LdByte(x, kk) => self.ld_vx_byte(x, kk),
+            Instruction::AddByte(x, kk) => self.add_vx_byte(x, kk),
+            Instruction::LdReg(x, y) => self.ld_vx_vy(x, y),
+            Instruction::Or(x, y) => self.or_vx_vy(x, y),
+            Instruction::And(x, y) => self.and_vx_vy(x, y),
+            Instruction::Xor(x, y) => self.xor_vx_vy(x, y),
+            Instruction::Add(x, y) => self.add_vx_vy(x, y),
+            Instruction::Sub(x, y) => self.sub_vx_vy(x, y),
+            Instruction::Shr(x, y) => self.shr_vx(x, y),
+            Instruction::Subn(x, y) => self.subn_vx_vy(x, y),
+            Instruction::Shl(x, y) => self.shl_vx(x, y),
+            Instruction::SneReg(x, y) => self.sne_vx_vy(bus, x, y),
+            Instruction::LdI(nnn) => self.ld_i_addr(nnn),
+            Instruction::JpV0(x, nnn) => self.jmp_v0_addr(x, nnn),
+            Instruction::Rnd(x, kk) => self.rnd_vx_byte(x, kk),
+            Instruction::Drw(x, y, n) => self.d_xn_n(bus, x, y, n).map_err(fault)?,
+            Instruction::Skp(x) => self.skp_vx(bus, x),
+            Instruction::Sknp(x) => self.sknp_vx(bus, x),
+            Instruction::LdILong(nnnn) => self.ld_i_long(nnnn),
+            Instruction::Plane(n) => self.plane_n(bus, n),
+            Instruction::Audio => self.audio(bus).map_err(fault)?,
+            Instruction::LdVxDt(x) => self.ld_vx_dt(x),
+            Instruction::LdVxK(x) => self.ld_vx_k(bus, x),
+            Instruction::LdDtVx(x) => self.ld_dt_vx(x),
+            Instruction::LdStVx(x) => self.ld_st_vx(x),
+            Instruction::AddIVx(x) => self.add_i_vx(x),
+            Instruction::LdFVx(x) => self.ld_f_vx(x),
+            Instruction::LdHfVx(x) => self.ld_hf_vx(x),
+            Instruction::LdBVx(x) => self.ld_b_vx(bus, x).map_err(fault)?,
+            Instruction::Pitch(x) => self.pitch_vx(x),
+            Instruction::LdIVx(x) => self.ld_i_vx(bus, x).map_err(fault)?,
+            Instruction::LdVxI(x) => self.ld_vx_i(bus, x).map_err(fault)?,
+            Instruction::LdRVx(x) => self.ld_r_vx(x),
+            Instruction::LdVxR(x) => self.ld_vx_r(x),
         };
 
-        self.pc = self.pc + 2;
+        self.pc = self.pc.wrapping_add(2);
+        Ok(())
+    }
+
+    // Skips the next instruction, XO-CHIP skips both words of F000 nnnn
+    fn skip(&mut self, bus: &crate::bus::DataBus) {
+        self.pc = self.pc.wrapping_add(2);
+        let next = bus.ram.mem.get(self.pc as usize..self.pc as usize + 2);
+        if self.quirks.xo_chip && next == Some(&[0xF0, 0x00]) {
+            self.pc = self.pc.wrapping_add(2);
+        }
     }
 
     #[inline(always)]
     fn instr_exec_time(&mut self, delay: u16) {
-        self.instr_time = delay as u128;
+        self.instr_time = delay as u64;
     }
 
     // 0x00E0 Clear screen
@@ -104,30 +205,78 @@ impl Cpu {
         self.instr_exec_time(109);
     }
 
+    // 0x00Cn Scroll display n lines down (SCHIP)
+    fn scd_n(&mut self, bus: &mut crate::bus::DataBus, n: u8) {
+        bus.display.scroll_down(n as usize);
+        self.instr_exec_time(109);
+    }
+
+    // 0x00Dn Scroll display n lines up (XO-CHIP)
+    fn scu_n(&mut self, bus: &mut crate::bus::DataBus, n: u8) {
+        bus.display.scroll_up(n as usize);
+        self.instr_exec_time(109);
+    }
+
+    // 0x00FB Scroll display 4 pixels right (SCHIP)
+    fn scr(&mut self, bus: &mut crate::bus::DataBus) {
+        bus.display.scroll_right(4);
+        self.instr_exec_time(109);
+    }
+
+    // 0x00FC Scroll display 4 pixels left (SCHIP)
+    fn scl(&mut self, bus: &mut crate::bus::DataBus) {
+        bus.display.scroll_left(4);
+        self.instr_exec_time(109);
+    }
+
+    // 0x00FD Exit the interpreter (SCHIP)
+    fn exit(&mut self) {
+        self.halted = true;
+        self.pc = self.pc.wrapping_sub(2);
+        self.instr_exec_time(109);
+    }
+
+    // 0x00FE Disable high resolution mode (SCHIP)
+    fn low(&mut self, bus: &mut crate::bus::DataBus) {
+        bus.display.set_hires(false);
+        self.instr_exec_time(109);
+    }
+
+    // 0x00FF Enable 128x64 high resolution mode (SCHIP)
+    fn high(&mut self, bus: &mut crate::bus::DataBus) {
+        bus.display.set_hires(true);
+        self.instr_exec_time(109);
+    }
+
     // 0x00EE Return from a subroutine
-    fn ret_s(&mut self) {
-        let addr: u16 = self.stack.pop().unwrap();
-        self.pc = addr - 2;
+    fn ret_s(&mut self) -> Result<(), Fault> {
+        let addr: u16 = self.stack.pop().ok_or(Fault::StackUnderflow)?;
+        self.pc = addr.wrapping_sub(2);
         self.instr_exec_time(109);
+        Ok(())
     }
 
     // 0x1 Jump to location nnn
     fn jp_addr(&mut self, nnn: u16) {
-        self.pc = nnn - 2;
+        self.pc = nnn.wrapping_sub(2);
         self.instr_exec_time(105);
     }
 
     // 0x2 Call subroutine at nnn
-    fn call_addr(&mut self, nnn: u16) {
-        self.stack.push(self.pc + 2);
-        self.pc = nnn - 2;
+    fn call_addr(&mut self, nnn: u16) -> Result<(), Fault> {
+        if self.stack.len() >= STACK_SIZE {
+            return Err(Fault::StackOverflow);
+        }
+        self.stack.push(self.pc.wrapping_add(2));
+        self.pc = nnn.wrapping_sub(2);
         self.instr_exec_time(105);
+        Ok(())
     }
 
     // 0x3 Skip next instruction if Vx = kk
-    fn se_vx_byte(&mut self, x: u8, kk: u8) {
+    fn se_vx_byte(&mut self, bus: &crate::bus::DataBus, x: u8, kk: u8) {
         if self.reg[x as usize] == kk {
-            self.pc += 2;
+            self.skip(bus);
             self.instr_exec_time(46);
             return;
         }
@@ -135,9 +284,9 @@ impl Cpu {
     }
 
     // 0x4 Skip next instruction if Vx != kk
-    fn sne_vx_byte(&mut self, x: u8, kk: u8) {
+    fn sne_vx_byte(&mut self, bus: &crate::bus::DataBus, x: u8, kk: u8) {
         if self.reg[x as usize] != kk {
-            self.pc += 2;
+            self.skip(bus);
             self.instr_exec_time(46);
             return;
         }
@@ -145,15 +294,44 @@ impl Cpu {
     }
 
     // 0x5 Skip next instruction if Vx = Vy
-    fn se_vx_vy(&mut self, x: u8, y: u8) {
+    fn se_vx_vy(&mut self, bus: &crate::bus::DataBus, x: u8, y: u8) {
         if self.reg[x as usize] == self.reg[y as usize] {
-            self.pc += 2;
+            self.skip(bus);
             self.instr_exec_time(64);
             return;
         }
         self.instr_exec_time(82);
     }
 
+    // 0x5XY2 Store registers Vx through Vy in memory starting at location I (XO-CHIP)
+    // The registers are stored in reverse order if x > y, I is not changed
+    fn ld_i_vx_vy(&mut self, bus: &mut crate::bus::DataBus, x: u8, y: u8) -> Result<(), Fault> {
+        let count = x.max(y) - x.min(y) + 1;
+        for offset in 0..count {
+            let r = if x <= y { x + offset } else { x - offset };
+            let address = self.i as usize + offset as usize;
+            bus.ram.write_bytes(address, &[self.reg[r as usize]])?;
+        }
+        self.instr_exec_time(count as u16 * 64);
+        Ok(())
+    }
+
+    // 0x5XY3 Read registers Vx through Vy from memory starting at location I (XO-CHIP)
+    fn ld_vx_vy_i(&mut self, bus: &crate::bus::DataBus, x: u8, y: u8) -> Result<(), Fault> {
+        let count = x.max(y) - x.min(y) + 1;
+        let bytes = bus.ram.read_bytes(self.i as usize, count as usize)?;
+        for (offset, byte) in bytes.iter().enumerate() {
+            let r = if x <= y {
+                x as usize + offset
+            } else {
+                x as usize - offset
+            };
+            self.reg[r] = *byte;
+        }
+        self.instr_exec_time(count as u16 * 64);
+        Ok(())
+    }
+
     // 0x6 Set Vx = kk
     fn ld_vx_byte(&mut self, vx: u8, byte: u8) {
         self.reg[vx as usize] = byte;
@@ -175,18 +353,27 @@ impl Cpu {
     // 0x8__1 Set Vx = Vx OR Vy
     fn or_vx_vy(&mut self, x: u8, y: u8) {
         self.reg[x as usize] |= self.reg[y as usize];
+        if self.quirks.vf_reset {
+            self.reg[VF] = 0;
+        }
         self.instr_exec_time(200);
     }
 
     // 0x8__2 Set Vx = Vx AND Vy
     fn and_vx_vy(&mut self, x: u8, y: u8) {
         self.reg[x as usize] &= self.reg[y as usize];
+        if self.quirks.vf_reset {
+            self.reg[VF] = 0;
+        }
         self.instr_exec_time(200);
     }
 
     // 0x8__3 Set Vx = Vx XOR Vy
     fn xor_vx_vy(&mut self, x: u8, y: u8) {
         self.reg[x as usize] ^= self.reg[y as usize];
+        if self.quirks.vf_reset {
+            self.reg[VF] = 0;
+        }
         self.instr_exec_time(200);
     }
 
@@ -209,11 +396,12 @@ impl Cpu {
         self.instr_exec_time(200);
     }
 
-    // 0x8__6 Set Vx = Vx SHR 1
-    // WARN: conflicting documentation. Using new implementation.
-    fn shr_vx(&mut self, x: u8) {
-        self.reg[VF] = self.reg[x as usize] & 0x01;
-        self.reg[x as usize] = self.reg[x as usize].wrapping_shr(1);
+    // 0x8__6 Set Vx = Vx SHR 1 (Vx = Vy SHR 1 with the shift_vy quirk)
+    fn shr_vx(&mut self, x: u8, y: u8) {
+        let src = if self.quirks.shift_vy { y } else { x };
+        let value = self.reg[src as usize];
+        self.reg[x as usize] = value.wrapping_shr(1);
+        self.reg[VF] = value & 0x01;
         self.instr_exec_time(200);
     }
 
@@ -228,18 +416,19 @@ impl Cpu {
         self.instr_exec_time(200);
     }
 
-    // 0x8__E Set Vx = Vx SHL 1
-    // WARN: Conflicting documentation. Using new implementation.
-    fn shl_vx(&mut self, x: u8) {
-        self.reg[VF] = (self.reg[x as usize] & 0b10000000) >> 7;
-        self.reg[x as usize] = self.reg[x as usize].wrapping_shl(1);
+    // 0x8__E Set Vx = Vx SHL 1 (Vx = Vy SHL 1 with the shift_vy quirk)
+    fn shl_vx(&mut self, x: u8, y: u8) {
+        let src = if self.quirks.shift_vy { y } else { x };
+        let value = self.reg[src as usize];
+        self.reg[x as usize] = value.wrapping_shl(1);
+        self.reg[VF] = (value & 0b10000000) >> 7;
         self.instr_exec_time(200);
     }
 
     // 0x9XY0 Skip next instruction if Vx != Vy
-    fn sne_vx_vy(&mut self, x: u8, y: u8) {
+    fn sne_vx_vy(&mut self, bus: &crate::bus::DataBus, x: u8, y: u8) {
         if self.reg[x as usize] != self.reg[y as usize] {
-            self.pc += 2;
+            self.skip(bus);
             self.instr_exec_time(64);
             return;
         }
@@ -252,34 +441,49 @@ impl Cpu {
         self.instr_exec_time(55);
     }
 
-    // 0xB Jump to location nnn + V0
-    fn jmp_v0_addr(&mut self, nnn: u16) {
-        self.pc = (nnn + self.reg[0] as u16) - 2;
+    // 0xB Jump to location nnn + V0 (xnn + Vx with the jump_vx quirk)
+    fn jmp_v0_addr(&mut self, x: u8, nnn: u16) {
+        let offset = if self.quirks.jump_vx {
+            self.reg[x as usize]
+        } else {
+            self.reg[0]
+        };
+        self.pc = (nnn + offset as u16).wrapping_sub(2);
         self.instr_exec_time(105);
     }
 
     // 0xC Set Vx = random byte AND kk
     fn rnd_vx_byte(&mut self, x: u8, kk: u8) {
-        let mut rng = rand::thread_rng();
-        self.reg[x as usize] = rng.gen_range(0..255) & kk;
+        self.reg[x as usize] = self.rng.next_u8() & kk;
         self.instr_exec_time(164);
     }
 
     // 0xD Dxyn - DRW Vx, Vy, nibble
-    fn d_xn_n(&mut self, bus: &mut crate::bus::DataBus, x: u8, y: u8, n: u8) {
-        let collision = bus.display.draw_s(
-            self.reg[x as usize],
-            self.reg[y as usize],
-            &bus.ram.mem[self.i as usize..(self.i + n as u16) as usize],
-        );
+    // Dxy0 draws a 16x16 sprite (SCHIP)
+    fn d_xn_n(&mut self, bus: &mut crate::bus::DataBus, x: u8, y: u8, n: u8) -> Result<(), Fault> {
+        let (vx, vy) = (self.reg[x as usize], self.reg[y as usize]);
+        let clip = self.quirks.clip_sprites;
+        // XO-CHIP reads one sprite per selected plane
+        let planes = bus.display.plane_count().max(1);
+        let i = self.i as usize;
+        let collision = if n == 0 {
+            let sprite = bus.ram.read_bytes(i, 32 * planes)?;
+            bus.display.draw_l(vx, vy, sprite, clip)
+        } else {
+            let sprite = bus.ram.read_bytes(i, n as usize * planes)?;
+            bus.display.draw_s(vx, vy, sprite, clip)
+        };
         self.reg[VF] = collision as u8; // VF set if collision found
+        self.wait_for_vblank = self.quirks.display_wait;
         self.instr_exec_time(22743);
+        Ok(())
     }
 
     // E_9E Skip next instruction if key with the value of Vx is pressed
+    // Only the low nibble of Vx selects the key, as on the COSMAC VIP
     fn skp_vx(&mut self, bus: &crate::bus::DataBus, x: u8) {
-        if bus.keyboard.keys[self.reg[x as usize] as usize] {
-            self.pc += 2;
+        if bus.keyboard.keys[(self.reg[x as usize] & 0x0F) as usize] {
+            self.skip(bus);
             self.instr_exec_time(82);
             return;
         }
@@ -288,24 +492,48 @@ impl Cpu {
 
     // E_A1 Skip next instruction if key with the value of Vx is not pressed
     fn sknp_vx(&mut self, bus: &crate::bus::DataBus, x: u8) {
-        if !bus.keyboard.keys[self.reg[x as usize] as usize] {
-            self.pc += 2;
+        if !bus.keyboard.keys[(self.reg[x as usize] & 0x0F) as usize] {
+            self.skip(bus);
             self.instr_exec_time(82);
             return;
         }
         self.instr_exec_time(64);
     }
 
+    // 0xF000 nnnn Set I = nnnn, the address is the next word (XO-CHIP)
+    fn ld_i_long(&mut self, nnnn: u16) {
+        self.i = nnnn;
+        self.pc += 2;
+        self.instr_exec_time(110);
+    }
+
+    // 0xFn01 Select the bitplanes n used for drawing, scrolling and clearing (XO-CHIP)
+    fn plane_n(&mut self, bus: &mut crate::bus::DataBus, n: u8) {
+        bus.display.planes = n & 0x03;
+        self.instr_exec_time(45);
+    }
+
+    // 0xF002 Load the 16 byte audio pattern at I (XO-CHIP)
+    fn audio(&mut self, bus: &crate::bus::DataBus) -> Result<(), Fault> {
+        let mut pattern = [0; 16];
+        pattern.copy_from_slice(bus.ram.read_bytes(self.i as usize, 16)?);
+        self.audio_pattern = Some(pattern);
+        self.instr_exec_time(1024);
+        Ok(())
+    }
+
     // 0xF_07 Set Vx = delay timer value
     fn ld_vx_dt(&mut self, x: u8) {
         self.reg[x as usize] = self.dt;
         self.instr_exec_time(45);
     }
 
-    // 0xF_0A Wait for a key press (blocking), store the value of the key in Vx
+    // 0xF_0A Wait for a key press and release (blocking), store the value of the key in Vx
     fn ld_vx_k(&mut self, bus: &mut crate::bus::DataBus, x: u8) {
         bus.keyboard.register = x;
+        bus.keyboard.wait_key = None;
         self.wait_for_input = true;
+        self.instr_exec_time(45);
     }
 
     // 0xF_15 Set delay timer = Vx
@@ -322,7 +550,7 @@ impl Cpu {
 
     // 0xF_1E Set I = I + Vx
     fn add_i_vx(&mut self, x: u8) {
-        self.i += self.reg[x as usize] as u16;
+        self.i = self.i.wrapping_add(self.reg[x as usize] as u16);
         self.instr_exec_time(86);
     }
 
@@ -332,34 +560,64 @@ impl Cpu {
         self.instr_exec_time(91);
     }
 
+    // 0xF_30 Set I = location of 10 byte sprite for digit Vx (SCHIP)
+    fn ld_hf_vx(&mut self, x: u8) {
+        self.i = BIG_FONT_ADDRESS + (self.reg[x as usize] & 0x0F) as u16 * 10;
+        self.instr_exec_time(91);
+    }
+
     // 0xF_33 Store BCD representation of Vx in memory locations I, I+1, and I+2
-    fn ld_b_vx(&mut self, bus: &mut crate::bus::DataBus, x: u8) {
+    fn ld_b_vx(&mut self, bus: &mut crate::bus::DataBus, x: u8) -> Result<(), Fault> {
         let hundreds = self.reg[x as usize] / 100;
         let tens = (self.reg[x as usize] % 100) / 10;
         let units = self.reg[x as usize] % 10;
-        bus.ram.write_byte(self.i as usize, hundreds);
-        bus.ram.write_byte((self.i + 1) as usize, tens);
-        bus.ram.write_byte((self.i + 2) as usize, units);
+        bus.ram
+            .write_bytes(self.i as usize, &[hundreds, tens, units])?;
         self.instr_exec_time((hundreds + tens + units) as u16 * 73 + 364);
+        Ok(())
+    }
+
+    // 0xF_3A Set the audio pattern pitch = Vx (XO-CHIP)
+    fn pitch_vx(&mut self, x: u8) {
+        self.pitch = self.reg[x as usize];
+        self.instr_exec_time(45);
     }
 
     // 0xF_55 Store registers V0 through Vx in memory starting at location I
-    //WARN: Conflicting documentation.
-    fn ld_i_vx(&mut self, bus: &mut crate::bus::DataBus, x: u8) {
-        let i = self.i as usize;
+    // I is set to I + x + 1 with the load_store_inc_i quirk
+    fn ld_i_vx(&mut self, bus: &mut crate::bus::DataBus, x: u8) -> Result<(), Fault> {
         let x = x as usize;
-        bus.ram.mem[i..i + x + 1].copy_from_slice(&self.reg[0..x + 1]);
-        //self.i += x as u16 + 1;
+        bus.ram.write_bytes(self.i as usize, &self.reg[0..x + 1])?;
+        if self.quirks.load_store_inc_i {
+            self.i = self.i.wrapping_add(x as u16 + 1);
+        }
         self.instr_exec_time((x as u16 * 64) + 64);
+        Ok(())
     }
 
     // 0xF_65 Read registers V0 through Vx from memory starting at location I
-    // WARN: Conflicting documentation.
-    fn ld_vx_i(&mut self, bus: &crate::bus::DataBus, x: u8) {
-        let i = self.i as usize;
+    // I is set to I + x + 1 with the load_store_inc_i quirk
+    fn ld_vx_i(&mut self, bus: &crate::bus::DataBus, x: u8) -> Result<(), Fault> {
+        let x = x as usize;
+        self.reg[0..x + 1].copy_from_slice(bus.ram.read_bytes(self.i as usize, x + 1)?);
+        if self.quirks.load_store_inc_i {
+            self.i = self.i.wrapping_add(x as u16 + 1);
+        }
+        self.instr_exec_time((x as u16 * 64) + 64);
+        Ok(())
+    }
+
+    // 0xF_75 Store registers V0 through Vx in the RPL user flags (SCHIP)
+    fn ld_r_vx(&mut self, x: u8) {
+        let x = x as usize;
+        self.rpl[0..x + 1].copy_from_slice(&self.reg[0..x + 1]);
+        self.instr_exec_time((x as u16 * 64) + 64);
+    }
+
+    // 0xF_85 Read registers V0 through Vx from the RPL user flags (SCHIP)
+    fn ld_vx_r(&mut self, x: u8) {
         let x = x as usize;
-        self.reg[0..x + 1].copy_from_slice(&bus.ram.mem[i..i + x + 1]);
-        //self.i += x as u16 + 1;
+        self.reg[0..x + 1].copy_from_slice(&self.rpl[0..x + 1]);
         self.instr_exec_time((x as u16 * 64) + 64);
     }
 }
@@ -0,0 +1,480 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::fmt::Write;
+
+use crate::chip8::Chip8;
+use crate::error::Chip8Error;
+use crate::ram::Access;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operand {
+    V(u8),
+    I,
+    Dt,
+    St,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+// Breakpoint condition such as "v3 == 5"
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Condition {
+    pub operand: Operand,
+    pub comparison: Comparison,
+    pub value: u16,
+}
+
+impl Condition {
+    pub fn holds(&self, chip8: &Chip8) -> bool {
+        let actual = match self.operand {
+            Operand::V(x) => chip8.cpu.reg[x as usize] as u16,
+            Operand::I => chip8.cpu.i(),
+            Operand::Dt => chip8.cpu.dt as u16,
+            Operand::St => chip8.cpu.st as u16,
+        };
+        match self.comparison {
+            Comparison::Eq => actual == self.value,
+            Comparison::Ne => actual != self.value,
+            Comparison::Lt => actual < self.value,
+            Comparison::Le => actual <= self.value,
+            Comparison::Gt => actual > self.value,
+            Comparison::Ge => actual >= self.value,
+        }
+    }
+
+    fn parse(words: &[&str]) -> Option<Self> {
+        let (operand, comparison, value) = match words {
+            [operand, comparison, value] => (*operand, *comparison, *value),
+            _ => return None,
+        };
+        let operand = match operand.to_lowercase().as_str() {
+            "i" => Operand::I,
+            "dt" => Operand::Dt,
+            "st" => Operand::St,
+            reg if reg.len() == 2 && reg.starts_with('v') => {
+                Operand::V(u8::from_str_radix(&reg[1..], 16).ok()?)
+            }
+            _ => return None,
+        };
+        let comparison = match comparison {
+            "==" => Comparison::Eq,
+            "!=" => Comparison::Ne,
+            "<" => Comparison::Lt,
+            "<=" => Comparison::Le,
+            ">" => Comparison::Gt,
+            ">=" => Comparison::Ge,
+            _ => return None,
+        };
+        Some(Self {
+            operand,
+            comparison,
+            value: parse_number(value).and_then(|v| u16::try_from(v).ok())?,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    Access,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub address: usize,
+    pub len: usize,
+    pub kind: WatchKind,
+}
+
+impl Watchpoint {
+    pub fn matches(&self, access: &Access) -> bool {
+        let kind = match self.kind {
+            WatchKind::Read => !access.write,
+            WatchKind::Write => access.write,
+            WatchKind::Access => true,
+        };
+        kind && access.address < self.address.saturating_add(self.len)
+            && self.address < access.address.saturating_add(access.len)
+    }
+}
+
+// Why execution was paused
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stop {
+    Breakpoint(u16),
+    Watchpoint { pc: u16, access: Access },
+    // A step over or step out finished
+    Step(u16),
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Stop::Breakpoint(pc) => write!(f, "breakpoint at {:#06X}", pc),
+            Stop::Watchpoint { pc, access } => write!(
+                f,
+                "watchpoint: {:#06X} {} {} byte(s) at {:#06X}",
+                pc,
+                if access.write { "wrote" } else { "read" },
+                access.len,
+                access.address
+            ),
+            Stop::Step(pc) => write!(f, "stopped at {:#06X}", pc),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum RunMode {
+    Continue,
+    // Run until the instruction after a call is reached at the same stack depth
+    StepOver { pc: u16, depth: usize },
+    // Run until the current subroutine returns
+    StepOut { depth: usize },
+}
+
+pub struct Debugger {
+    // Breakpoint addresses with an optional condition
+    pub breakpoints: BTreeMap<u16, Option<Condition>>,
+    pub watchpoints: Vec<Watchpoint>,
+    pub paused: bool,
+    mode: RunMode,
+    // Breakpoint to ignore once when resuming from it
+    resume_pc: Option<u16>,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self {
+            breakpoints: BTreeMap::new(),
+            watchpoints: Vec::new(),
+            paused: false,
+            mode: RunMode::Continue,
+            resume_pc: None,
+        }
+    }
+
+    // Runs Chip8::exec_cycle unless paused, returns the reason if execution stopped
+    pub fn exec_cycle(&mut self, chip8: &mut Chip8) -> Result<Option<Stop>, Chip8Error> {
+        if self.paused {
+            return Ok(None);
+        }
+        let pc = chip8.cpu.pc;
+        if self.resume_pc != Some(pc) && self.breakpoint_hit(chip8, pc) {
+            return Ok(Some(self.pause(Stop::Breakpoint(pc))));
+        }
+
+        let executed = chip8.instructions;
+        chip8.bus.ram.trace = !self.watchpoints.is_empty();
+        chip8.exec_cycle()?;
+        if chip8.instructions == executed {
+            return Ok(None);
+        }
+        self.resume_pc = None;
+        Ok(self.after_instruction(chip8, pc))
+    }
+
+    pub fn pause(&mut self, stop: Stop) -> Stop {
+        self.paused = true;
+        self.mode = RunMode::Continue;
+        stop
+    }
+
+    pub fn resume(&mut self, chip8: &Chip8) {
+        self.paused = false;
+        self.resume_pc = Some(chip8.cpu.pc);
+    }
+
+    fn breakpoint_hit(&self, chip8: &Chip8, pc: u16) -> bool {
+        match self.breakpoints.get(&pc) {
+            Some(Some(condition)) => condition.holds(chip8),
+            Some(None) => true,
+            None => false,
+        }
+    }
+
+    fn after_instruction(&mut self, chip8: &mut Chip8, pc: u16) -> Option<Stop> {
+        let accesses: Vec<Access> = chip8.bus.ram.accesses.borrow_mut().drain(..).collect();
+        for access in accesses {
+            if self.watchpoints.iter().any(|w| w.matches(&access)) {
+                return Some(self.pause(Stop::Watchpoint { pc, access }));
+            }
+        }
+
+        let depth = chip8.cpu.stack().len();
+        match self.mode {
+            RunMode::StepOver { pc, depth: d } if chip8.cpu.pc == pc && depth <= d => {
+                Some(self.pause(Stop::Step(pc)))
+            }
+            RunMode::StepOut { depth: d } if depth < d => {
+                Some(self.pause(Stop::Step(chip8.cpu.pc)))
+            }
+            _ => None,
+        }
+    }
+
+    // Executes a debugger command and returns its output
+    pub fn command(&mut self, chip8: &mut Chip8, line: &str) -> String {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let result = match words.as_slice() {
+            [] => Ok(String::new()),
+            ["c"] | ["continue"] => {
+                self.resume(chip8);
+                Ok("continuing".to_string())
+            }
+            ["p"] | ["pause"] => {
+                self.pause(Stop::Step(chip8.cpu.pc));
+                Ok(self.registers(chip8))
+            }
+            ["s"] | ["step"] => self.step(chip8, 1),
+            ["s", n] | ["step", n] => match parse_number(n) {
+                Some(n) => self.step(chip8, n),
+                None => Err(format!("invalid count '{}'", n)),
+            },
+            ["n"] | ["next"] => self.step_over(chip8),
+            ["f"] | ["finish"] => self.step_out(chip8),
+            ["b", addr, rest @ ..] | ["break", addr, rest @ ..] => {
+                self.add_breakpoint(chip8, addr, rest)
+            }
+            ["d", addr] | ["delete", addr] => match parse_number(addr).map(u16::try_from) {
+                Some(Ok(addr)) if self.breakpoints.remove(&addr).is_some() => {
+                    Ok(format!("deleted breakpoint at {:#06X}", addr))
+                }
+                _ => Err(format!("no breakpoint at {}", addr)),
+            },
+            ["w", kind, addr, rest @ ..] | ["watch", kind, addr, rest @ ..] => {
+                self.add_watchpoint(chip8, kind, addr, rest)
+            }
+            ["dw", addr] => match parse_number(addr) {
+                Some(addr) => {
+                    self.watchpoints.retain(|w| w.address != addr);
+                    Ok(format!("deleted watchpoints at {:#06X}", addr))
+                }
+                None => Err(format!("invalid address '{}'", addr)),
+            },
+            ["l"] | ["list"] => Ok(self.list()),
+            ["r"] | ["regs"] => Ok(self.registers(chip8)),
+            ["stack"] => Ok(Self::stack(chip8)),
+            ["m", addr, rest @ ..] | ["mem", addr, rest @ ..] => Self::memory(chip8, addr, rest),
+            ["h"] | ["help"] => Ok(HELP.to_string()),
+            _ => Err(format!("unknown command '{}', try help", line.trim())),
+        };
+        result.unwrap_or_else(|e| format!("error: {}", e))
+    }
+
+    fn step(&mut self, chip8: &mut Chip8, count: usize) -> Result<String, String> {
+        self.paused = true;
+        chip8.bus.ram.trace = !self.watchpoints.is_empty();
+        for _ in 0..count {
+            let pc = chip8.cpu.pc;
+            chip8.exec_cycle().map_err(|e| e.to_string())?;
+            if let Some(stop) = self.after_instruction(chip8, pc) {
+                return Ok(format!("{}\n{}", stop, self.registers(chip8)));
+            }
+        }
+        Ok(self.registers(chip8))
+    }
+
+    fn step_over(&mut self, chip8: &mut Chip8) -> Result<String, String> {
+        let pc = chip8.cpu.pc as usize;
+        match chip8.bus.ram.mem.get(pc) {
+            Some(byte) if byte & 0xF0 == 0x20 => {
+                self.mode = RunMode::StepOver {
+                    pc: chip8.cpu.pc.wrapping_add(2),
+                    depth: chip8.cpu.stack().len(),
+                };
+                self.resume(chip8);
+                Ok("stepping over call".to_string())
+            }
+            _ => self.step(chip8, 1),
+        }
+    }
+
+    fn step_out(&mut self, chip8: &mut Chip8) -> Result<String, String> {
+        let depth = chip8.cpu.stack().len();
+        if depth == 0 {
+            return Err("not in a subroutine".to_string());
+        }
+        self.mode = RunMode::StepOut { depth };
+        self.resume(chip8);
+        Ok("stepping out".to_string())
+    }
+
+    fn add_breakpoint(
+        &mut self,
+        chip8: &Chip8,
+        addr: &str,
+        rest: &[&str],
+    ) -> Result<String, String> {
+        let addr = parse_address(chip8, addr)? as u16;
+        let condition = match rest {
+            [] => None,
+            ["if", condition @ ..] => Some(
+                Condition::parse(condition)
+                    .ok_or(format!("invalid condition '{}'", condition.join(" ")))?,
+            ),
+            _ => return Err("expected 'if <reg> <op> <value>'".to_string()),
+        };
+        self.breakpoints.insert(addr, condition);
+        Ok(format!("breakpoint at {:#06X}", addr))
+    }
+
+    fn add_watchpoint(
+        &mut self,
+        chip8: &Chip8,
+        kind: &str,
+        addr: &str,
+        rest: &[&str],
+    ) -> Result<String, String> {
+        let kind = match kind {
+            "r" => WatchKind::Read,
+            "w" => WatchKind::Write,
+            "rw" => WatchKind::Access,
+            _ => {
+                return Err(format!(
+                    "invalid watch kind '{}', expected r, w or rw",
+                    kind
+                ))
+            }
+        };
+        let address = parse_address(chip8, addr)?;
+        let len = match rest {
+            [] => 1,
+            [len] => parse_number(len)
+                .filter(|len| *len > 0)
+                .ok_or(format!("invalid length '{}'", len))?,
+            _ => return Err("expected 'w <r|w|rw> <addr> [len]'".to_string()),
+        };
+        if len > chip8.bus.ram.mem.len() - address {
+            return Err(format!("{:#06X} + {:#X} is out of memory", address, len));
+        }
+        self.watchpoints.push(Watchpoint { address, len, kind });
+        Ok(format!("watching {:#06X}..{:#06X}", address, address + len))
+    }
+
+    fn list(&self) -> String {
+        let mut out = String::new();
+        for (addr, condition) in self.breakpoints.iter() {
+            match condition {
+                Some(c) => writeln!(
+                    out,
+                    "break {:#06X} if {:?} {:?} {:#X}",
+                    addr, c.operand, c.comparison, c.value
+                ),
+                None => writeln!(out, "break {:#06X}", addr),
+            }
+            .unwrap();
+        }
+        for w in self.watchpoints.iter() {
+            writeln!(
+                out,
+                "watch {:?} {:#06X}..{:#06X}",
+                w.kind,
+                w.address,
+                w.address.saturating_add(w.len)
+            )
+            .unwrap();
+        }
+        out.trim_end().to_string()
+    }
+
+    pub fn registers(&self, chip8: &Chip8) -> String {
+        let cpu = &chip8.cpu;
+        let mut out = format!(
+            "PC {:#06X}  I {:#06X}  DT {:02X}  ST {:02X}  SP {}",
+            cpu.pc,
+            cpu.i(),
+            cpu.dt,
+            cpu.st,
+            cpu.stack().len()
+        );
+        for (x, value) in cpu.reg.iter().enumerate() {
+            let sep = if x % 8 == 0 { "\n" } else { "  " };
+            write!(out, "{}V{:X} {:02X}", sep, x, value).unwrap();
+        }
+        out
+    }
+
+    fn stack(chip8: &Chip8) -> String {
+        if chip8.cpu.stack().is_empty() {
+            return "stack is empty".to_string();
+        }
+        let mut out = String::new();
+        for (depth, addr) in chip8.cpu.stack().iter().enumerate().rev() {
+            writeln!(out, "{:2}: {:#06X}", depth, addr).unwrap();
+        }
+        out.trim_end().to_string()
+    }
+
+    fn memory(chip8: &Chip8, addr: &str, rest: &[&str]) -> Result<String, String> {
+        let start = parse_number(addr).ok_or(format!("invalid address '{}'", addr))?;
+        let len = match rest {
+            [] => 64,
+            [len] => parse_number(len).ok_or(format!("invalid length '{}'", len))?,
+            _ => return Err("expected 'mem <addr> [len]'".to_string()),
+        };
+        let mem = &chip8.bus.ram.mem;
+        let end = start.saturating_add(len).min(mem.len());
+        if start >= end {
+            return Err(format!("{:#06X} is out of memory", start));
+        }
+        let mut out = String::new();
+        for (row, bytes) in mem[start..end].chunks(16).enumerate() {
+            write!(out, "{:#06X}:", start + row * 16).unwrap();
+            for byte in bytes {
+                write!(out, " {:02X}", byte).unwrap();
+            }
+            out.push('\n');
+        }
+        Ok(out.trim_end().to_string())
+    }
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// An address inside the machine's memory
+fn parse_address(chip8: &Chip8, text: &str) -> Result<usize, String> {
+    match parse_number(text) {
+        Some(addr) if addr < chip8.bus.ram.mem.len() => Ok(addr),
+        Some(addr) => Err(format!("{:#06X} is out of memory", addr)),
+        None => Err(format!("invalid address '{}'", text)),
+    }
+}
+
+// Numbers are hexadecimal, with or without a 0x prefix
+fn parse_number(text: &str) -> Option<usize> {
+    let digits = text.trim_start_matches("0x").trim_start_matches("0X");
+    usize::from_str_radix(digits, 16).ok()
+}
+
+const HELP: &str = "\
+c, continue               resume execution
+p, pause                  pause execution
+s, step [n]               execute n instructions
+n, next                   step over a call
+f, finish                 run until the current subroutine returns
+b, break <addr> [if <reg> <op> <value>]
+                          set a breakpoint, reg is v0-vf, i, dt or st
+d, delete <addr>          delete a breakpoint
+w, watch <r|w|rw> <addr> [len]
+                          break on memory reads and/or writes
+dw <addr>                 delete the watchpoints at addr
+l, list                   list breakpoints and watchpoints
+r, regs                   dump registers, I and timers
+stack                     dump the call stack
+m, mem <addr> [len]       dump memory
+numbers are hexadecimal";
@@ -0,0 +1,202 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use crate::chip8::ROM_ADDRESS;
+use crate::instruction::Instruction;
+
+// Walks every path reachable from the entry point, anything never reached is listed as data.
+// Bnnn jumps are computed, only nnn itself is followed as the start of a jump table.
+pub fn reachable(rom: &[u8], xo_chip: bool) -> (BTreeMap<u16, Instruction>, BTreeSet<u16>) {
+    let mut code = BTreeMap::new();
+    let mut labels = BTreeSet::new();
+    let mut pending = vec![ROM_ADDRESS as u16];
+
+    while let Some(addr) = pending.pop() {
+        if code.contains_key(&addr) {
+            continue;
+        }
+        let instruction = match decode_at(rom, addr, xo_chip) {
+            Some(instruction) => instruction,
+            None => continue,
+        };
+        code.insert(addr, instruction);
+        let next = addr.wrapping_add(instruction.size());
+
+        match instruction {
+            Instruction::Jp(nnn) => {
+                labels.insert(nnn);
+                pending.push(nnn);
+            }
+            Instruction::Call(nnn) => {
+                labels.insert(nnn);
+                pending.push(nnn);
+                pending.push(next);
+            }
+            Instruction::JpV0(_, nnn) => {
+                labels.insert(nnn);
+                pending.push(nnn);
+            }
+            Instruction::Ret | Instruction::Exit => {}
+            Instruction::SeByte(..)
+            | Instruction::SneByte(..)
+            | Instruction::SeReg(..)
+            | Instruction::SneReg(..)
+            | Instruction::Skp(_)
+            | Instruction::Sknp(_) => {
+                pending.push(next);
+                // XO-CHIP skips over both words of F000 nnnn
+                let skipped = decode_at(rom, next, xo_chip).map_or(2, |i| i.size());
+                pending.push(next.wrapping_add(skipped));
+            }
+            _ => pending.push(next),
+        }
+    }
+    (code, labels)
+}
+
+fn decode_at(rom: &[u8], addr: u16, xo_chip: bool) -> Option<Instruction> {
+    let word = |addr: usize| {
+        let offset = addr.checked_sub(ROM_ADDRESS)?;
+        let bytes = rom.get(offset..offset + 2)?;
+        Some((bytes[0] as u16) << 8 | bytes[1] as u16)
+    };
+    Instruction::decode(word(addr as usize)?, word(addr as usize + 2), xo_chip)
+}
+
+// One line per instruction or data byte, data bytes show their bits to make sprites visible
+pub fn disassemble(rom: &[u8], xo_chip: bool) -> String {
+    let (code, labels) = reachable(rom, xo_chip);
+    let mut out = String::new();
+    let mut offset = 0;
+
+    while offset < rom.len() {
+        let addr = (ROM_ADDRESS + offset) as u16;
+        if labels.contains(&addr) {
+            writeln!(out, "L{:03X}:", addr).unwrap();
+        }
+        match code.get(&addr) {
+            Some(instruction) => {
+                let size = instruction.size() as usize;
+                let hex: String = rom[offset..offset + size]
+                    .iter()
+                    .map(|b| format!("{:02X}", b))
+                    .collect();
+                writeln!(out, "{:#06X}  {:<8}  {}", addr, hex, instruction).unwrap();
+                offset += size;
+            }
+            None => {
+                let byte = rom[offset];
+                let bits: String = (0..8)
+                    .map(|bit| if byte & (0x80 >> bit) != 0 { '#' } else { '.' })
+                    .collect();
+                writeln!(
+                    out,
+                    "{:#06X}  {:02X}        db {:#04X}  ; {}",
+                    addr, byte, byte, bits
+                )
+                .unwrap();
+                offset += 1;
+            }
+        }
+    }
+    out
+}
+
+// The same listing as Octo source that the assembler turns back into the rom.
+// Addresses stay numeric, the labels only mark the jump and call targets.
+pub fn to_octo(rom: &[u8], xo_chip: bool) -> String {
+    let (code, labels) = reachable(rom, xo_chip);
+    let mut out = String::new();
+    let mut offset = 0;
+
+    while offset < rom.len() {
+        let addr = (ROM_ADDRESS + offset) as u16;
+        if labels.contains(&addr) {
+            writeln!(out, ": L{:03X}", addr).unwrap();
+        }
+        match code
+            .get(&addr)
+            .and_then(|i| octo(i).map(|text| (i.size(), text)))
+        {
+            Some((size, text)) => {
+                writeln!(out, "  {:<24}# {:#06X}", text, addr).unwrap();
+                offset += size as usize;
+            }
+            None => {
+                let byte = rom[offset];
+                let bits: String = (0..8)
+                    .map(|bit| if byte & (0x80 >> bit) != 0 { '1' } else { '.' })
+                    .collect();
+                writeln!(
+                    out,
+                    "  {:<24}# {:#06X} {}",
+                    format!("{:#04X}", byte),
+                    addr,
+                    bits
+                )
+                .unwrap();
+                offset += 1;
+            }
+        }
+    }
+    out
+}
+
+// Octo statement for an instruction, None when Octo has no way to write it
+fn octo(instruction: &Instruction) -> Option<String> {
+    let text = match *instruction {
+        Instruction::ScrollDown(n) => format!("scroll-down {}", n),
+        Instruction::ScrollUp(n) => format!("scroll-up {}", n),
+        Instruction::Cls => "clear".to_string(),
+        Instruction::Ret => "return".to_string(),
+        Instruction::ScrollRight => "scroll-right".to_string(),
+        Instruction::ScrollLeft => "scroll-left".to_string(),
+        Instruction::Exit => "exit".to_string(),
+        Instruction::Lores => "lores".to_string(),
+        Instruction::Hires => "hires".to_string(),
+        Instruction::Jp(nnn) => format!("jump {:#05X}", nnn),
+        Instruction::Call(nnn) => format!(":call {:#05X}", nnn),
+        // Octo's if ... then skips the next instruction when the condition is false
+        Instruction::SeByte(x, kk) => format!("if v{:x} != {:#04X} then", x, kk),
+        Instruction::SneByte(x, kk) => format!("if v{:x} == {:#04X} then", x, kk),
+        Instruction::SeReg(x, y) => format!("if v{:x} != v{:x} then", x, y),
+        Instruction::SaveRange(x, y) => format!("save v{:x} - v{:x}", x, y),
+        Instruction::LoadRange(x, y) => format!("load v{:x} - v{:x}", x, y),
+        Instruction::LdByte(x, kk) => format!("v{:x} := {:#04X}", x, kk),
+        Instruction::AddByte(x, kk) => format!("v{:x} += {:#04X}", x, kk),
+        Instruction::LdReg(x, y) => format!("v{:x} := v{:x}", x, y),
+        Instruction::Or(x, y) => format!("v{:x} |= v{:x}", x, y),
+        Instruction::And(x, y) => format!("v{:x} &= v{:x}", x, y),
+        Instruction::Xor(x, y) => format!("v{:x} ^= v{:x}", x, y),
+        Instruction::Add(x, y) => format!("v{:x} += v{:x}", x, y),
+        Instruction::Sub(x, y) => format!("v{:x} -= v{:x}", x, y),
+        Instruction::Shr(x, y) => format!("v{:x} >>= v{:x}", x, y),
+        Instruction::Subn(x, y) => format!("v{:x} =- v{:x}", x, y),
+        Instruction::Shl(x, y) => format!("v{:x} <<= v{:x}", x, y),
+        Instruction::SneReg(x, y) => format!("if v{:x} == v{:x} then", x, y),
+        Instruction::LdI(nnn) => format!("i := {:#05X}", nnn),
+        Instruction::JpV0(_, nnn) => format!("jump0 {:#05X}", nnn),
+        Instruction::Rnd(x, kk) => format!("v{:x} := random {:#04X}", x, kk),
+        Instruction::Drw(x, y, n) => format!("sprite v{:x} v{:x} {}", x, y, n),
+        Instruction::Skp(x) => format!("if v{:x} -key then", x),
+        Instruction::Sknp(x) => format!("if v{:x} key then", x),
+        Instruction::LdILong(nnnn) => format!("i := long {:#06X}", nnnn),
+        Instruction::Plane(n) if n <= 3 => format!("plane {}", n),
+        Instruction::Plane(_) => return None,
+        Instruction::Audio => "audio".to_string(),
+        Instruction::LdVxDt(x) => format!("v{:x} := delay", x),
+        Instruction::LdVxK(x) => format!("v{:x} := key", x),
+        Instruction::LdDtVx(x) => format!("delay := v{:x}", x),
+        Instruction::LdStVx(x) => format!("buzzer := v{:x}", x),
+        Instruction::AddIVx(x) => format!("i += v{:x}", x),
+        Instruction::LdFVx(x) => format!("i := hex v{:x}", x),
+        Instruction::LdHfVx(x) => format!("i := bighex v{:x}", x),
+        Instruction::LdBVx(x) => format!("bcd v{:x}", x),
+        Instruction::Pitch(x) => format!("pitch := v{:x}", x),
+        Instruction::LdIVx(x) => format!("save v{:x}", x),
+        Instruction::LdVxI(x) => format!("load v{:x}", x),
+        Instruction::LdRVx(x) => format!("saveflags v{:x}", x),
+        Instruction::LdVxR(x) => format!("loadflags v{:x}", x),
+    };
+    Some(text)
+}
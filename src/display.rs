@@ -1,61 +1,266 @@
+use std::io::Write;
+
+use crate::hash::fnv1a;
+use crate::palette::Colors;
+use crate::state::{StateError, StateReader, StateWriter};
+
+pub const WIDTH: usize = 128;
+pub const HEIGHT: usize = 64;
+pub const PLANES: usize = 2;
+
+// The framebuffer is always sized for the SCHIP high resolution mode,
+// in low resolution mode only the top-left 64x32 pixels are used.
+// Each pixel holds one bit per XO-CHIP bitplane, giving a color index 0-3.
 pub struct Display {
-    pub framebuffer: [[bool; 64]; 32],
+    pub framebuffer: [[u8; WIDTH]; HEIGHT],
+    pub hires: bool,
+    // Bitmask of the planes affected by drawing, scrolling and clearing
+    pub planes: u8,
     pub req_draw: bool,
 }
 
 impl Display {
     pub fn new() -> Self {
         Self {
-            framebuffer: [[false; 64]; 32],
+            framebuffer: [[0; WIDTH]; HEIGHT],
+            hires: false,
+            planes: 0b01,
             req_draw: false,
         }
     }
 
-    pub fn draw_s(&mut self, vx: u8, vy: u8, bytes: &[u8]) -> bool {
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.bool(self.hires);
+        w.u8(self.planes);
+        for row in self.framebuffer.iter() {
+            w.bytes(row);
+        }
+    }
+
+    pub fn read_state(r: &mut StateReader) -> Result<Self, StateError> {
+        let mut display = Self::new();
+        display.hires = r.bool()?;
+        display.planes = r.u8()?;
+        if display.planes > 0b11 {
+            return Err(StateError::Invalid("plane selection"));
+        }
+        for row in display.framebuffer.iter_mut() {
+            row.copy_from_slice(r.bytes(WIDTH)?);
+            if row.iter().any(|pixel| *pixel > 0b11) {
+                return Err(StateError::Invalid("framebuffer"));
+            }
+        }
+        display.req_draw = true;
+        Ok(display)
+    }
+
+    pub fn width(&self) -> usize {
+        if self.hires {
+            WIDTH
+        } else {
+            WIDTH / 2
+        }
+    }
+
+    pub fn height(&self) -> usize {
+        if self.hires {
+            HEIGHT
+        } else {
+            HEIGHT / 2
+        }
+    }
+
+    // Switching resolution clears every plane
+    pub fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        self.framebuffer = [[0; WIDTH]; HEIGHT];
+        self.req_draw = true;
+    }
+
+    pub fn plane_count(&self) -> usize {
+        self.planes.count_ones() as usize
+    }
+
+    // 8xN sprite, one byte per row
+    // With several planes selected the sprite data for each plane follows one another
+    pub fn draw_s(&mut self, vx: u8, vy: u8, bytes: &[u8], clip: bool) -> bool {
+        self.draw_planes(vx, vy, bytes, 1, clip)
+    }
+
+    // 16x16 sprite, two bytes per row
+    pub fn draw_l(&mut self, vx: u8, vy: u8, bytes: &[u8], clip: bool) -> bool {
+        self.draw_planes(vx, vy, bytes, 2, clip)
+    }
+
+    fn draw_planes(&mut self, vx: u8, vy: u8, bytes: &[u8], row_bytes: usize, clip: bool) -> bool {
+        let mut collision = false;
+        if let Some(len) = bytes.len().checked_div(self.plane_count()) {
+            let mut sprites = bytes.chunks(len.max(1));
+            for plane in 0..PLANES {
+                let mask = 1 << plane;
+                if self.planes & mask != 0 {
+                    let sprite = sprites.next().unwrap_or(&[]);
+                    collision |= self.draw_sprite(vx, vy, sprite, row_bytes, mask, clip);
+                }
+            }
+        }
+        self.req_draw = true;
+        collision
+    }
+
+    // The sprite origin always wraps, the pixels past the edges are either
+    // clipped or wrapped around to the other side
+    fn draw_sprite(
+        &mut self,
+        vx: u8,
+        vy: u8,
+        bytes: &[u8],
+        row_bytes: usize,
+        mask: u8,
+        clip: bool,
+    ) -> bool {
+        let (width, height) = (self.width(), self.height());
         let mut collision = false;
-        for j in 0..bytes.len() {
-            let row = bytes[j];
-            for i in 0..8 {
-                let new_pixel = row >> (7 - i) & 0x01;
-                if new_pixel == 1 {
-                    let xi = (vx.wrapping_add(i)) % 64;
-                    let yj = (vy.wrapping_add(j as u8)) % 32;
-                    let old_pixel = self.get_pixel(xi as usize, yj as usize);
-                    if old_pixel {
-                        collision = true;
+        let x0 = vx as usize % width;
+        let y0 = vy as usize % height;
+        for (j, row) in bytes.chunks(row_bytes).enumerate() {
+            for (b, byte) in row.iter().enumerate() {
+                for i in 0..8 {
+                    let new_pixel = byte >> (7 - i) & 0x01;
+                    if new_pixel == 1 {
+                        let (mut xi, mut yj) = (x0 + b * 8 + i, y0 + j);
+                        if clip && (xi >= width || yj >= height) {
+                            continue;
+                        }
+                        xi %= width;
+                        yj %= height;
+                        let old_pixel = self.get_pixel(xi, yj);
+                        if old_pixel & mask != 0 {
+                            collision = true;
+                        }
+                        self.set_pixel(xi, yj, old_pixel ^ mask);
                     }
-                    self.set_pixel(xi as usize, yj as usize, (new_pixel == 1) ^ old_pixel);
                 }
             }
         }
+        collision
+    }
+
+    // Moves the selected planes by (dx, dy), pixels moved in from outside the screen are off
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let (width, height) = (self.width() as isize, self.height() as isize);
+        let old = self.framebuffer;
+        for y in 0..height {
+            for x in 0..width {
+                let (sx, sy) = (x - dx, y - dy);
+                let moved = if sx >= 0 && sx < width && sy >= 0 && sy < height {
+                    old[sy as usize][sx as usize] & self.planes
+                } else {
+                    0
+                };
+                let kept = old[y as usize][x as usize] & !self.planes;
+                self.set_pixel(x as usize, y as usize, kept | moved);
+            }
+        }
         self.req_draw = true;
-        return collision;
     }
 
-    #[allow(dead_code)]
+    pub fn scroll_down(&mut self, n: usize) {
+        self.scroll(0, n as isize);
+    }
+
+    pub fn scroll_up(&mut self, n: usize) {
+        self.scroll(0, -(n as isize));
+    }
+
+    pub fn scroll_right(&mut self, n: usize) {
+        self.scroll(n as isize, 0);
+    }
+
+    pub fn scroll_left(&mut self, n: usize) {
+        self.scroll(-(n as isize), 0);
+    }
+
     pub fn debug_draw(&self) {
-        for col in self.framebuffer.iter() {
-            for c in col.iter() {
-                if *c == true {
-                    print!("#")
-                } else {
-                    print!(".")
-                }
+        print!("{}", self.to_text());
+    }
+
+    // One character per visible pixel: . when off, #, + or * for plane 1, plane 2 or both
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for col in self.framebuffer.iter().take(self.height()) {
+            for c in col.iter().take(self.width()) {
+                text.push(match c {
+                    0 => '.',
+                    1 => '#',
+                    2 => '+',
+                    _ => '*',
+                });
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    // Identifies the picture for regression tests, covers the resolution and the visible pixels
+    pub fn hash(&self) -> u64 {
+        let mut data = vec![self.hires as u8];
+        for col in self.framebuffer.iter().take(self.height()) {
+            data.extend_from_slice(&col[..self.width()]);
+        }
+        fnv1a(&data)
+    }
+
+    // The visible picture as RGBA with every pixel scaled up to a scale x scale square
+    pub fn to_rgba(&self, scale: usize, colors: &Colors) -> Vec<u8> {
+        let (width, height) = (self.width() * scale, self.height() * scale);
+        let mut rgba = Vec::with_capacity(width * height * 4);
+        for y in 0..height {
+            for x in 0..width {
+                let pixel = self.framebuffer[y / scale][x / scale];
+                rgba.extend_from_slice(&colors[(pixel & 3) as usize]);
             }
-            println!("");
         }
+        rgba
+    }
+
+    pub fn write_png<W: Write>(
+        &self,
+        scale: usize,
+        colors: &Colors,
+        w: W,
+    ) -> Result<(), png::EncodingError> {
+        let scale = scale.max(1);
+        let width = (self.width() * scale) as u32;
+        let height = (self.height() * scale) as u32;
+        let mut encoder = png::Encoder::new(w, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.to_rgba(scale, colors))
     }
 
+    // Clears the selected planes
     pub fn clear_screen(&mut self) {
-        self.framebuffer = [[false; 64]; 32];
+        for row in self.framebuffer.iter_mut() {
+            for pixel in row.iter_mut() {
+                *pixel &= !self.planes;
+            }
+        }
         self.req_draw = true;
     }
 
-    pub fn set_pixel(&mut self, x: usize, y: usize, value: bool) {
+    pub fn set_pixel(&mut self, x: usize, y: usize, value: u8) {
         self.framebuffer[y][x] = value;
     }
 
-    pub fn get_pixel(&self, x: usize, y: usize) -> bool {
-        self.framebuffer[y][x] == true
+    pub fn get_pixel(&self, x: usize, y: usize) -> u8 {
+        self.framebuffer[y][x]
+    }
+}
+
+impl Default for Display {
+    fn default() -> Self {
+        Self::new()
     }
 }
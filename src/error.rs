@@ -0,0 +1,88 @@
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Chip8Error {
+    UnknownOpcode {
+        pc: u16,
+        instruction: u16,
+    },
+    StackUnderflow {
+        pc: u16,
+        instruction: u16,
+    },
+    StackOverflow {
+        pc: u16,
+        instruction: u16,
+    },
+    MemoryOutOfBounds {
+        pc: u16,
+        instruction: u16,
+        address: usize,
+    },
+    PcOutOfRange {
+        pc: u16,
+    },
+    RomTooLarge {
+        size: usize,
+        capacity: usize,
+    },
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Chip8Error::UnknownOpcode { pc, instruction } => {
+                write!(f, "{:#06X}: unknown opcode {:#06X}", pc, instruction)
+            }
+            Chip8Error::StackUnderflow { pc, instruction } => {
+                write!(
+                    f,
+                    "{:#06X}: {:#06X} returned with an empty stack",
+                    pc, instruction
+                )
+            }
+            Chip8Error::StackOverflow { pc, instruction } => {
+                write!(f, "{:#06X}: {:#06X} overflowed the stack", pc, instruction)
+            }
+            Chip8Error::MemoryOutOfBounds {
+                pc,
+                instruction,
+                address,
+            } => write!(
+                f,
+                "{:#06X}: {:#06X} accessed memory out of bounds at {:#06X}",
+                pc, instruction, address
+            ),
+            Chip8Error::PcOutOfRange { pc } => write!(f, "{:#06X}: pc out of range", pc),
+            Chip8Error::RomTooLarge { size, capacity } => write!(
+                f,
+                "rom of {} bytes does not fit in {} bytes of memory",
+                size, capacity
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Chip8Error {}
+
+// Raised by the instruction handlers, exec_instruction adds the pc and instruction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    StackUnderflow,
+    StackOverflow,
+    MemoryOutOfBounds(usize),
+}
+
+impl Fault {
+    pub fn at(self, pc: u16, instruction: u16) -> Chip8Error {
+        match self {
+            Fault::StackUnderflow => Chip8Error::StackUnderflow { pc, instruction },
+            Fault::StackOverflow => Chip8Error::StackOverflow { pc, instruction },
+            Fault::MemoryOutOfBounds(address) => Chip8Error::MemoryOutOfBounds {
+                pc,
+                instruction,
+                address,
+            },
+        }
+    }
+}
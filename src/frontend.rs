@@ -0,0 +1,640 @@
+use std::fs;
+use std::io::BufRead;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use pixels::{Pixels, SurfaceTexture};
+use winit::dpi::PhysicalSize;
+use winit::event::{Event, VirtualKeyCode};
+use winit::event_loop::{ControlFlow, EventLoop};
+use winit::window::{Fullscreen, WindowBuilder};
+use winit_input_helper::WinitInputHelper;
+
+use chip8::audio::{AudioSink, NullSink, WavSink, Waveform};
+#[cfg(feature = "audio")]
+use chip8::audio_device::DeviceSink;
+use chip8::debugger::Debugger;
+use chip8::display::{HEIGHT, WIDTH};
+use chip8::movie::Movie;
+use chip8::phosphor::Phosphor;
+use chip8::rewind::Rewind;
+use chip8::screenshot;
+use chip8::{Chip8, Quirks};
+
+use self::config::Config;
+use self::gamepad::Gamepads;
+use crate::{find_palette, parse_quirks, WAV_SAMPLE_RATE};
+
+// The TOML config with the keymaps and palettes
+mod config;
+// Gamepad bindings, read through gilrs with the gamepad feature
+mod gamepad;
+// Host keys to CHIP-8 keys
+mod keymap;
+
+// Shift+F1-F8 saves the machine to a numbered slot, F1-F8 loads it back
+const SLOT_KEYS: [VirtualKeyCode; 8] = [
+    VirtualKeyCode::F1,
+    VirtualKeyCode::F2,
+    VirtualKeyCode::F3,
+    VirtualKeyCode::F4,
+    VirtualKeyCode::F5,
+    VirtualKeyCode::F6,
+    VirtualKeyCode::F7,
+    VirtualKeyCode::F8,
+];
+
+const QUIT_KEY: VirtualKeyCode = VirtualKeyCode::Escape;
+// Held to step the machine backwards one frame at a time
+const REWIND_KEY: VirtualKeyCode = VirtualKeyCode::Back;
+// Breaks into the debugger when running with --debug
+const BREAK_KEY: VirtualKeyCode = VirtualKeyCode::F10;
+// Switches to the next palette
+const PALETTE_KEY: VirtualKeyCode = VirtualKeyCode::F9;
+// Saves the screen to a timestamped PNG
+const SCREENSHOT_KEY: VirtualKeyCode = VirtualKeyCode::F12;
+// Where SCREENSHOT_KEY saves to when no --screenshot-dir is given
+const SCREENSHOT_DIR: &str = "screenshots";
+// Toggles borderless fullscreen
+const FULLSCREEN_KEY: VirtualKeyCode = VirtualKeyCode::F11;
+// Initial window size in window pixels per low resolution pixel
+const DEFAULT_SCALE: u32 = 10;
+const FRAME_DURATION: Duration = Duration::from_nanos(16666667);
+// Longest stretch of real time emulated at once, so a stall doesn't fast forward the game
+const MAX_CATCH_UP: Duration = Duration::from_millis(100);
+// Read at startup when no --config is given, the emulator runs with the defaults without it
+const DEFAULT_CONFIG: &str = "chip8.toml";
+
+// The windowed emulator, args are the command line without the program name
+pub fn run(mut args: impl Iterator<Item = String>) {
+    let mut filename = "games/INVADERS".to_string();
+    let mut quirks = Quirks::default();
+    let mut state_file = None;
+    let mut rewind_seconds = 10;
+    let mut debug = false;
+    let mut seed = None;
+    let mut record_file = None;
+    let mut play_file = None;
+    let mut mute = false;
+    let mut tone = None;
+    let mut volume = None;
+    let mut waveform = None;
+    let mut wav_file = None;
+    let mut config_file = None;
+    let mut palette_name = None;
+    let mut phosphor_decay = None;
+    let mut scale = DEFAULT_SCALE;
+    let mut fullscreen = false;
+    let mut screenshot_dir = SCREENSHOT_DIR.to_string();
+    let mut screenshot_scale = 1;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--quirks" => quirks = parse_quirks(&args.next().unwrap_or_default()),
+            "--load-state" => state_file = args.next(),
+            "--debug" => debug = true,
+            "--record" => record_file = args.next(),
+            "--play" => play_file = args.next(),
+            "--mute" => mute = true,
+            "--wav" => wav_file = args.next(),
+            "--config" => config_file = args.next(),
+            "--palette" => palette_name = args.next(),
+            "--tone" => {
+                let value = args.next().unwrap_or_default();
+                tone = Some(value.parse::<f32>().unwrap_or_else(|_| {
+                    eprintln!("Invalid tone '{}', expected a frequency in Hz", value);
+                    std::process::exit(1);
+                }));
+            }
+            "--volume" => {
+                let value = args.next().unwrap_or_default();
+                volume = Some(
+                    value
+                        .parse::<u8>()
+                        .ok()
+                        .filter(|v| *v <= 100)
+                        .unwrap_or_else(|| {
+                            eprintln!("Invalid volume '{}', expected 0 to 100", value);
+                            std::process::exit(1);
+                        }),
+                );
+            }
+            "--fullscreen" => fullscreen = true,
+            "--screenshot-dir" => screenshot_dir = args.next().unwrap_or_default(),
+            "--screenshot-scale" => {
+                let value = args.next().unwrap_or_default();
+                screenshot_scale = value.parse().ok().filter(|s| *s > 0).unwrap_or_else(|| {
+                    eprintln!("Invalid scale '{}', expected a whole number from 1", value);
+                    std::process::exit(1);
+                });
+            }
+            "--scale" => {
+                let value = args.next().unwrap_or_default();
+                scale = value.parse().ok().filter(|s| *s > 0).unwrap_or_else(|| {
+                    eprintln!("Invalid scale '{}', expected a whole number from 1", value);
+                    std::process::exit(1);
+                });
+            }
+            "--phosphor" => {
+                let value = args.next().unwrap_or_default();
+                phosphor_decay = Some(
+                    value
+                        .parse::<u8>()
+                        .ok()
+                        .filter(|v| *v <= 100)
+                        .unwrap_or_else(|| {
+                            eprintln!("Invalid phosphor decay '{}', expected 0 to 100", value);
+                            std::process::exit(1);
+                        }),
+                );
+            }
+            "--waveform" => {
+                let value = args.next().unwrap_or_default();
+                waveform = Some(Waveform::parse(&value).unwrap_or_else(|| {
+                    eprintln!(
+                        "Invalid waveform '{}', expected square, triangle, sawtooth or sine",
+                        value
+                    );
+                    std::process::exit(1);
+                }));
+            }
+            "--seed" => {
+                let value = args.next().unwrap_or_default();
+                seed = Some(value.parse().unwrap_or_else(|_| {
+                    eprintln!("Invalid seed '{}', expected a number", value);
+                    std::process::exit(1);
+                }));
+            }
+            "--rewind" => {
+                let seconds = args.next().unwrap_or_default();
+                rewind_seconds = seconds.parse().unwrap_or_else(|_| {
+                    eprintln!("Invalid rewind length '{}', expected seconds", seconds);
+                    std::process::exit(1);
+                });
+            }
+            _ => filename = arg,
+        }
+    }
+
+    let game_data = fs::read(&filename).unwrap_or_else(|e| {
+        eprintln!("Could not read {}: {}", filename, e);
+        std::process::exit(1);
+    });
+
+    let config = load_config(config_file);
+    let rom_name = std::path::Path::new(&filename)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let keymap = config.keymap(&rom_name);
+    let padmap = config.padmap(&rom_name);
+    let palettes = config.palettes().unwrap_or_else(|e| {
+        eprintln!("Invalid config: {}", e);
+        std::process::exit(1);
+    });
+    let palette_name = palette_name.or_else(|| config.palette(&rom_name).map(String::from));
+    let mut palette_index = match palette_name {
+        Some(name) => find_palette(&palettes, &name),
+        None => 0,
+    };
+    seed = seed.or_else(|| config.seed(&rom_name));
+    let mut phosphor = phosphor_decay
+        .or_else(|| config.phosphor(&rom_name))
+        .filter(|decay| *decay > 0)
+        .map(|decay| Phosphor::new(decay as f32 / 100.0));
+
+    // Movies start from power on with the seed and quirks they were recorded with
+    let mut playback = play_file.map(|path| {
+        fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| {
+                Movie::parse(&text)
+                    .and_then(|movie| movie.check_rom(&game_data).map(|_| movie))
+                    .map_err(|e| e.to_string())
+            })
+            .unwrap_or_else(|e| {
+                eprintln!("Could not load movie {}: {}", path, e);
+                std::process::exit(1);
+            })
+    });
+    if let Some(movie) = &playback {
+        quirks = movie.quirks;
+        seed = Some(movie.seed);
+    }
+    if (record_file.is_some() || playback.is_some()) && state_file.is_some() {
+        eprintln!("Movies start from power on, --load-state can't be used with --record or --play");
+        std::process::exit(1);
+    }
+
+    let mut chip8 = Chip8::new(quirks);
+    if let Some(seed) = seed {
+        chip8.seed(seed);
+    }
+    if let Err(e) = chip8.load_rom(&game_data) {
+        eprintln!("Could not load {}: {}", filename, e);
+        std::process::exit(1);
+    }
+
+    let mut audio_sink = open_audio(mute);
+    chip8.audio.sample_rate = audio_sink.sample_rate();
+    // The recording gets the same samples as the sound device
+    let mut wav = wav_file.map(|path| {
+        if chip8.audio.sample_rate == 0 {
+            chip8.audio.sample_rate = WAV_SAMPLE_RATE;
+        }
+        let sink = WavSink::create(&path, chip8.audio.sample_rate).unwrap_or_else(|e| {
+            eprintln!("Could not create {}: {}", path, e);
+            std::process::exit(1);
+        });
+        (path, sink)
+    });
+    if let Some(tone) = tone {
+        chip8.audio.beeper.frequency = tone;
+    }
+    if let Some(volume) = volume {
+        chip8.audio.beeper.volume = volume as f32 / 100.0;
+    }
+    if let Some(waveform) = waveform {
+        chip8.audio.beeper.waveform = waveform;
+    }
+
+    let mut recording =
+        record_file.map(|path| (path, Movie::new(&game_data, chip8.cpu.rng.state, quirks)));
+
+    if let Some(path) = state_file {
+        let loaded = fs::read(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| chip8.load_state(&data).map_err(|e| e.to_string()));
+        if let Err(e) = loaded {
+            eprintln!("Could not load state {}: {}", path, e);
+            std::process::exit(1);
+        }
+    }
+
+    // The debugger starts paused and reads its commands from stdin
+    let mut debugger = None;
+    let (command_tx, commands) = mpsc::channel();
+    if debug {
+        let mut d = Debugger::new();
+        d.paused = true;
+        println!("{}", d.registers(&chip8));
+        println!("Debugger paused, type help for the commands");
+        debugger = Some(d);
+        thread::spawn(move || {
+            for line in std::io::stdin().lock().lines() {
+                if line.map(|l| command_tx.send(l)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    let mut input = WinitInputHelper::new();
+    let event_loop = EventLoop::new();
+    // The scale is in low resolution pixels, but pixels only scales the high resolution
+    // buffer by whole factors, so odd scales round up to the next even one. Physical pixels
+    // keep the size a whole multiple of the buffer on scaled displays too.
+    let buffer_scale = scale.div_ceil(2);
+    let window = WindowBuilder::new()
+        .with_title("Chip-8 Emulator")
+        .with_inner_size(PhysicalSize::new(
+            WIDTH as u32 * buffer_scale,
+            HEIGHT as u32 * buffer_scale,
+        ))
+        .with_min_inner_size(PhysicalSize::new(WIDTH as u32, HEIGHT as u32))
+        .with_resizable(true)
+        .build(&event_loop)
+        .unwrap();
+    if fullscreen {
+        window.set_fullscreen(Some(Fullscreen::Borderless(window.current_monitor())));
+    }
+
+    let window_size = window.inner_size();
+    let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, &window);
+
+    // The pixel buffer has the high resolution size, pixels scales it up by the largest
+    // whole factor that fits the window and letterboxes the rest
+    let mut pixels = Pixels::new(WIDTH as u32, HEIGHT as u32, surface_texture).unwrap();
+    let mut halted = false;
+    let mut rewind = Rewind::new(rewind_seconds * 60);
+    let mut rewinding = false;
+    let mut last_rewind = Instant::now();
+    let mut last_frame = chip8.frame;
+    if let Some(phosphor) = phosphor.as_mut() {
+        phosphor.reset(&chip8.bus.display, &palettes[palette_index].colors);
+    }
+    let mut last_update = Instant::now();
+
+    // CHIP-8 keys held on the keyboard
+    let mut held_keys = 0;
+    let mut gamepads = Gamepads::new()
+        .map_err(|e| eprintln!("Gamepads unavailable: {}", e))
+        .ok();
+
+    event_loop.run(move |event, _, control_flow| {
+        if let Event::LoopDestroyed = event {
+            if let Some((path, movie)) = &recording {
+                match fs::write(path, movie.to_string()) {
+                    Ok(()) => println!("Movie saved to {}", path),
+                    Err(e) => eprintln!("Could not save movie {}: {}", path, e),
+                }
+            }
+            if let Some((path, sink)) = wav.take() {
+                match sink.finish() {
+                    Ok(()) => println!("Audio saved to {}", path),
+                    Err(e) => eprintln!("Could not save audio {}: {}", path, e),
+                }
+            }
+            return;
+        }
+
+        if input.update(&event) {
+            held_keys = keymap.mask(&input);
+            if input.key_released(QUIT_KEY) || input.quit() {
+                *control_flow = ControlFlow::Exit;
+            }
+
+            let slot = handle_save_slots(&input, &mut chip8, &filename);
+            if let Some((_, result)) = &slot {
+                let message = result.clone().unwrap_or_else(|e| e);
+                eprintln!("{}", message);
+                window.set_title(&format!("Chip-8 Emulator - {}", message));
+            }
+            // Only a loaded state replaces the machine, a failed load leaves it untouched
+            if let Some((SlotAction::Load, Ok(_))) = slot {
+                halted = false;
+                rewind.clear();
+                if let Some(phosphor) = phosphor.as_mut() {
+                    phosphor.reset(&chip8.bus.display, &palettes[palette_index].colors);
+                }
+                last_frame = chip8.frame;
+                if let Some((_, movie)) = recording.as_mut() {
+                    movie.truncate(chip8.frame);
+                }
+            }
+
+            rewinding = input.key_held(REWIND_KEY) || input.key_pressed(REWIND_KEY);
+
+            // A minimized window reports a 0x0 size, which pixels can't resize to
+            if let Some(size) = input.window_resized() {
+                if size.width > 0 && size.height > 0 {
+                    pixels.resize(size.width, size.height);
+                    window.request_redraw();
+                }
+            }
+
+            if input.key_pressed(SCREENSHOT_KEY) {
+                let colors = &palettes[palette_index].colors;
+                let dir = std::path::Path::new(&screenshot_dir);
+                let display = &chip8.bus.display;
+                let message =
+                    match screenshot::save(display, screenshot_scale, colors, dir, &rom_name) {
+                        Ok(path) => format!("Screenshot saved to {}", path.display()),
+                        Err(e) => format!("Could not save screenshot: {}", e),
+                    };
+                eprintln!("{}", message);
+                window.set_title(&format!("Chip-8 Emulator - {}", message));
+            }
+
+            if input.key_pressed(FULLSCREEN_KEY) {
+                match window.fullscreen() {
+                    Some(_) => window.set_fullscreen(None),
+                    None => {
+                        let monitor = window.current_monitor();
+                        window.set_fullscreen(Some(Fullscreen::Borderless(monitor)));
+                    }
+                }
+            }
+
+            if input.key_pressed(PALETTE_KEY) {
+                palette_index = (palette_index + 1) % palettes.len();
+                let name = &palettes[palette_index].name;
+                window.set_title(&format!("Chip-8 Emulator - palette {}", name));
+                if let Some(phosphor) = phosphor.as_mut() {
+                    phosphor.reset(&chip8.bus.display, &palettes[palette_index].colors);
+                }
+                window.request_redraw();
+            }
+
+            if let Some(debugger) = debugger.as_mut() {
+                if input.key_pressed(BREAK_KEY) && !debugger.paused {
+                    debugger.paused = true;
+                    println!("paused\n{}", debugger.registers(&chip8));
+                }
+            }
+        }
+
+        if let Some(debugger) = debugger.as_mut() {
+            if let Ok(line) = commands.try_recv() {
+                println!("{}", debugger.command(&mut chip8, &line));
+                if let Some(phosphor) = phosphor.as_mut() {
+                    phosphor.reset(&chip8.bus.display, &palettes[palette_index].colors);
+                }
+                window.request_redraw();
+            }
+        }
+
+        if let Event::RedrawRequested(_) = event {
+            let colors = &palettes[palette_index].colors;
+            let display = &chip8.bus.display;
+            match phosphor.as_ref() {
+                Some(phosphor) => draw(pixels.get_frame(), display.width(), |x, y| {
+                    phosphor.pixel(x, y)
+                }),
+                None => draw(pixels.get_frame(), display.width(), |x, y| {
+                    colors[(display.framebuffer[y][x] & 3) as usize]
+                }),
+            }
+            if pixels.render().is_err() {
+                *control_flow = ControlFlow::Exit;
+                return;
+            }
+        }
+
+        // Step back one frame every 60th of a second while the rewind key is held
+        if rewinding {
+            if last_rewind.elapsed() >= FRAME_DURATION {
+                if let Some(state) = rewind.pop() {
+                    // The machine stays on the current frame if the state doesn't load
+                    if let Err(e) = chip8.load_state(&state) {
+                        eprintln!("Rewind stopped: {}", e);
+                        window.set_title(&format!("Chip-8 Emulator - rewind stopped: {}", e));
+                        rewind.clear();
+                        rewinding = false;
+                        return;
+                    }
+                    if let Some(phosphor) = phosphor.as_mut() {
+                        phosphor.update(&chip8.bus.display, &palettes[palette_index].colors);
+                    }
+                    window.request_redraw();
+                    halted = false;
+                    last_frame = chip8.frame;
+                    if let Some((_, movie)) = recording.as_mut() {
+                        movie.truncate(chip8.frame);
+                    }
+                }
+                last_rewind = Instant::now();
+            }
+            last_update = Instant::now();
+            return;
+        }
+
+        // Keep the window open on the last frame after an emulation error
+        if halted {
+            return;
+        }
+
+        // Advance the virtual clock by the real time since the last update
+        let now = Instant::now();
+        let target = chip8.clock + (now - last_update).min(MAX_CATCH_UP).as_micros() as u64;
+        last_update = now;
+        while chip8.clock < target {
+            let clock = chip8.clock;
+            let result = match debugger.as_mut() {
+                Some(debugger) => debugger.exec_cycle(&mut chip8).map(|stop| {
+                    if let Some(stop) = stop {
+                        println!("{}\n{}", stop, debugger.registers(&chip8));
+                    }
+                }),
+                None => chip8.exec_cycle(),
+            };
+            if let Err(error) = result {
+                eprintln!("Emulation halted: {}", error);
+                window.set_title(&format!("Chip-8 Emulator - halted: {}", error));
+                halted = true;
+                return;
+            }
+
+            if chip8.cpu.halted {
+                *control_flow = ControlFlow::Exit;
+                return;
+            }
+
+            // Input only changes at frame boundaries so movies replay exactly
+            if chip8.frame != last_frame {
+                let keys = match &playback {
+                    Some(movie) => movie.keys_at(chip8.frame),
+                    None => {
+                        let pads = gamepads.as_mut().map_or(0, |pads| pads.mask(&padmap));
+                        held_keys | pads
+                    }
+                };
+                if let Some((_, movie)) = recording.as_mut() {
+                    movie.record(chip8.frame, keys);
+                }
+                chip8.set_keys(keys);
+                rewind.push(chip8.save_state());
+                if let Some(phosphor) = phosphor.as_mut() {
+                    phosphor.update(&chip8.bus.display, &palettes[palette_index].colors);
+                    window.request_redraw();
+                }
+                last_frame = chip8.frame;
+
+                if matches!(&playback, Some(movie) if chip8.frame >= movie.length) {
+                    println!("Movie finished at frame {}", chip8.frame);
+                    playback = None;
+                }
+            }
+
+            // Paused in the debugger
+            if chip8.clock == clock {
+                break;
+            }
+        }
+
+        if let Some((_, sink)) = wav.as_mut() {
+            sink.write(&chip8.audio.samples);
+        }
+        chip8.audio.flush(audio_sink.as_mut());
+
+        // With phosphor blending the picture only changes at frame boundaries
+        if chip8.bus.display.req_draw {
+            if phosphor.is_none() {
+                window.request_redraw();
+            }
+            chip8.bus.display.req_draw = false;
+        }
+    });
+}
+
+// A missing default config is fine, a missing --config file is an error
+fn load_config(path: Option<String>) -> Config {
+    let (path, required) = match path {
+        Some(path) => (path, true),
+        None => (DEFAULT_CONFIG.to_string(), false),
+    };
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if required || e.kind() != std::io::ErrorKind::NotFound => {
+            eprintln!("Could not read config {}: {}", path, e);
+            std::process::exit(1);
+        }
+        Err(_) => return Config::default(),
+    };
+    Config::parse(&text).unwrap_or_else(|e| {
+        eprintln!("Invalid config {}: {}", path, e);
+        std::process::exit(1);
+    })
+}
+
+// Sound needs the audio feature, without it the emulator stays silent
+#[cfg(feature = "audio")]
+fn open_audio(mute: bool) -> Box<dyn AudioSink> {
+    if mute {
+        return Box::new(NullSink);
+    }
+    match DeviceSink::open() {
+        Ok(sink) => Box::new(sink),
+        Err(e) => {
+            eprintln!("No sound: {}", e);
+            Box::new(NullSink)
+        }
+    }
+}
+
+#[cfg(not(feature = "audio"))]
+fn open_audio(_mute: bool) -> Box<dyn AudioSink> {
+    Box::new(NullSink)
+}
+
+enum SlotAction {
+    Save,
+    Load,
+}
+
+fn handle_save_slots(
+    input: &WinitInputHelper,
+    chip8: &mut Chip8,
+    rom: &str,
+) -> Option<(SlotAction, Result<String, String>)> {
+    let slot = SLOT_KEYS.iter().position(|key| input.key_pressed(*key))? + 1;
+    let path = format!("{}.state{}", rom, slot);
+
+    let (action, result) = if input.held_shift() {
+        let saved = fs::write(&path, chip8.save_state())
+            .map(|_| format!("saved slot {}", slot))
+            .map_err(|e| e.to_string());
+        (SlotAction::Save, saved)
+    } else {
+        let loaded = fs::read(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| chip8.load_state(&data).map_err(|e| e.to_string()))
+            .map(|_| format!("loaded slot {}", slot));
+        (SlotAction::Load, loaded)
+    };
+
+    Some((action, result.map_err(|e| format!("slot {}: {}", slot, e))))
+}
+
+// Fills the high resolution sized frame, low resolution pixels cover 2x2 of it.
+// pixel gives the color of a display pixel.
+fn draw<F: Fn(usize, usize) -> [u8; 4]>(frame: &mut [u8], width: usize, pixel: F) {
+    let scale = WIDTH / width;
+    for (i, rgba) in frame.chunks_exact_mut(4).enumerate() {
+        let x = i % WIDTH;
+        let y = i / WIDTH;
+
+        rgba.copy_from_slice(&pixel(x / scale, y / scale));
+    }
+}
@@ -0,0 +1,207 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::Deserialize;
+use winit::event::VirtualKeyCode;
+
+use chip8::palette::{self, Palette};
+
+use super::gamepad::{PadInput, Padmap};
+use super::keymap::Keymap;
+
+// Frontend settings, read from a TOML file:
+//
+// palette = "amber"
+// phosphor = 60
+// seed = 1234
+//
+// [palettes]
+// paper = ["#F0EAD6", "#222222"]
+//
+// [keys]
+// 5 = ["W", "Up"]
+// 8 = ["S", "Down"]
+//
+// [gamepad]
+// 0 = ["East"]
+//
+// [roms.PONG]
+// palette = "paper"
+// phosphor = 0
+// seed = 7
+//
+// [roms.PONG.keys]
+// 1 = ["W"]
+// 4 = ["S"]
+//
+// [roms.PONG.gamepad]
+// 1 = ["DPadUp", "LeftStickUp"]
+// 4 = ["DPadDown", "LeftStickDown"]
+//
+// [keys] binds CHIP-8 keys 0-F to one or more host keys, named as winit's VirtualKeyCode,
+// [gamepad] to gamepad buttons and stick directions. Keys they don't list keep the default
+// layout. The [roms.<file name>] tables are applied on top of them for a single rom.
+// [palettes] adds palettes of 2 or 4 colors to the built-in ones, palette picks the
+// one to start with. phosphor blends the frames of flickering games, the percentage of
+// brightness a pixel keeps each frame after going dark. seed makes the random numbers of
+// Cxkk repeat across runs like --seed.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub palette: Option<String>,
+    pub phosphor: Option<u8>,
+    pub seed: Option<u64>,
+    pub palettes: BTreeMap<String, Vec<String>>,
+    pub keys: Bindings<VirtualKeyCode>,
+    pub gamepad: Bindings<PadInput>,
+    pub roms: BTreeMap<String, RomConfig>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RomConfig {
+    pub palette: Option<String>,
+    pub phosphor: Option<u8>,
+    pub seed: Option<u64>,
+    pub keys: Bindings<VirtualKeyCode>,
+    pub gamepad: Bindings<PadInput>,
+}
+
+// CHIP-8 key as a hex digit to host inputs
+pub type Bindings<T> = BTreeMap<String, Vec<T>>;
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Parse(toml::de::Error),
+    // A binding for something other than 0-F
+    BadKey(String),
+    BadPalette(String),
+    // A phosphor decay over 100 percent
+    BadPhosphor(u8),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Parse(e) => write!(f, "{}", e),
+            ConfigError::BadKey(key) => {
+                write!(f, "'{}' is not a CHIP-8 key, expected 0 to F", key)
+            }
+            ConfigError::BadPalette(message) => write!(f, "{}", message),
+            ConfigError::BadPhosphor(decay) => {
+                write!(
+                    f,
+                    "phosphor decay {} is out of range, expected 0 to 100",
+                    decay
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    pub fn parse(text: &str) -> Result<Self, ConfigError> {
+        let config: Config = toml::from_str(text).map_err(ConfigError::Parse)?;
+        let keys = config.roms.values().flat_map(|rom| rom.keys.keys());
+        let buttons = config.roms.values().flat_map(|rom| rom.gamepad.keys());
+        for key in config
+            .keys
+            .keys()
+            .chain(config.gamepad.keys())
+            .chain(keys)
+            .chain(buttons)
+        {
+            key_index(key).ok_or_else(|| ConfigError::BadKey(key.clone()))?;
+        }
+
+        let palettes = config.palettes()?;
+        let chosen = config.roms.values().map(|rom| &rom.palette);
+        for name in std::iter::once(&config.palette).chain(chosen).flatten() {
+            if !palettes.iter().any(|palette| &palette.name == name) {
+                let message = format!("unknown palette '{}'", name);
+                return Err(ConfigError::BadPalette(message));
+            }
+        }
+
+        let decays = config.roms.values().map(|rom| &rom.phosphor);
+        for decay in std::iter::once(&config.phosphor).chain(decays).flatten() {
+            if *decay > 100 {
+                return Err(ConfigError::BadPhosphor(*decay));
+            }
+        }
+        Ok(config)
+    }
+
+    // The default layout with [keys] and the overrides for the rom file name applied
+    pub fn keymap(&self, rom: &str) -> Keymap {
+        let mut keymap = Keymap::new();
+        bind(&mut keymap.keys, &self.keys);
+        if let Some(rom) = self.roms.get(rom) {
+            bind(&mut keymap.keys, &rom.keys);
+        }
+        keymap
+    }
+
+    // The built-in palettes followed by the ones from the config, a palette from the config
+    // replaces a built-in one with the same name
+    pub fn palettes(&self) -> Result<Vec<Palette>, ConfigError> {
+        let mut palettes = palette::builtin();
+        for (name, colors) in &self.palettes {
+            let colors: Vec<&str> = colors.iter().map(|c| c.as_str()).collect();
+            let custom = Palette::parse(name, &colors).map_err(ConfigError::BadPalette)?;
+            match palettes.iter_mut().find(|p| &p.name == name) {
+                Some(palette) => *palette = custom,
+                None => palettes.push(custom),
+            }
+        }
+        Ok(palettes)
+    }
+
+    // Name of the palette to start the rom with
+    pub fn palette(&self, rom: &str) -> Option<&str> {
+        self.roms
+            .get(rom)
+            .and_then(|rom| rom.palette.as_deref())
+            .or(self.palette.as_deref())
+    }
+
+    // Phosphor decay in percent for the rom, None leaves the blending off
+    pub fn phosphor(&self, rom: &str) -> Option<u8> {
+        self.roms
+            .get(rom)
+            .and_then(|rom| rom.phosphor)
+            .or(self.phosphor)
+    }
+
+    // Seed of the random number generator for the rom, None leaves it unseeded
+    pub fn seed(&self, rom: &str) -> Option<u64> {
+        self.roms.get(rom).and_then(|rom| rom.seed).or(self.seed)
+    }
+
+    // The default gamepad layout with [gamepad] and the overrides for the rom applied
+    pub fn padmap(&self, rom: &str) -> Padmap {
+        let mut padmap = Padmap::new();
+        bind(&mut padmap.keys, &self.gamepad);
+        if let Some(rom) = self.roms.get(rom) {
+            bind(&mut padmap.keys, &rom.gamepad);
+        }
+        padmap
+    }
+}
+
+fn bind<T: Clone>(keys: &mut [Vec<T>; 16], bindings: &Bindings<T>) {
+    for (key, inputs) in bindings {
+        if let Some(index) = key_index(key) {
+            keys[index] = inputs.clone();
+        }
+    }
+}
+
+fn key_index(key: &str) -> Option<usize> {
+    match key.len() {
+        1 => usize::from_str_radix(key, 16).ok(),
+        _ => None,
+    }
+}
@@ -0,0 +1,139 @@
+use serde::Deserialize;
+
+// Stick deflection that counts as a press
+#[cfg(feature = "gamepad")]
+const AXIS_THRESHOLD: f32 = 0.5;
+
+// A gamepad button or stick direction, the buttons are named as in gilrs
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum PadInput {
+    South,
+    East,
+    North,
+    West,
+    LeftTrigger,
+    LeftTrigger2,
+    RightTrigger,
+    RightTrigger2,
+    Select,
+    Start,
+    LeftThumb,
+    RightThumb,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+    LeftStickUp,
+    LeftStickDown,
+    LeftStickLeft,
+    LeftStickRight,
+    RightStickUp,
+    RightStickDown,
+    RightStickLeft,
+    RightStickRight,
+}
+
+// Gamepad inputs bound to each of the 16 CHIP-8 keys
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Padmap {
+    pub keys: [Vec<PadInput>; 16],
+}
+
+impl Padmap {
+    // The d-pad and left stick on 2/4/6/8 and the south button on 5, the keys most games
+    // move and fire with
+    pub fn new() -> Self {
+        use PadInput::*;
+        let mut keys: [Vec<PadInput>; 16] = Default::default();
+        keys[0x2] = vec![DPadUp, LeftStickUp];
+        keys[0x4] = vec![DPadLeft, LeftStickLeft];
+        keys[0x5] = vec![South];
+        keys[0x6] = vec![DPadRight, LeftStickRight];
+        keys[0x8] = vec![DPadDown, LeftStickDown];
+        Self { keys }
+    }
+}
+
+impl Default for Padmap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Every connected gamepad, read together as one
+#[cfg(feature = "gamepad")]
+pub struct Gamepads {
+    gilrs: gilrs::Gilrs,
+}
+
+#[cfg(feature = "gamepad")]
+impl Gamepads {
+    pub fn new() -> Result<Self, String> {
+        let gilrs = gilrs::Gilrs::new().map_err(|e| e.to_string())?;
+        Ok(Self { gilrs })
+    }
+
+    // The held keys as a Keyboard::mask
+    pub fn mask(&mut self, padmap: &Padmap) -> u16 {
+        // Events update the gamepad state, only the state is used
+        while self.gilrs.next_event().is_some() {}
+
+        let mut mask = 0;
+        for (_, pad) in self.gilrs.gamepads() {
+            for (i, inputs) in padmap.keys.iter().enumerate() {
+                if inputs.iter().any(|input| is_pressed(&pad, *input)) {
+                    mask |= 1 << i;
+                }
+            }
+        }
+        mask
+    }
+}
+
+// Without the gamepad feature no gamepad is ever connected
+#[cfg(not(feature = "gamepad"))]
+pub struct Gamepads;
+
+#[cfg(not(feature = "gamepad"))]
+impl Gamepads {
+    pub fn new() -> Result<Self, String> {
+        Ok(Self)
+    }
+
+    pub fn mask(&mut self, _padmap: &Padmap) -> u16 {
+        0
+    }
+}
+
+#[cfg(feature = "gamepad")]
+fn is_pressed(pad: &gilrs::Gamepad, input: PadInput) -> bool {
+    use gilrs::{Axis, Button};
+    let axis = |axis: Axis, direction: f32| pad.value(axis) * direction > AXIS_THRESHOLD;
+    match input {
+        PadInput::South => pad.is_pressed(Button::South),
+        PadInput::East => pad.is_pressed(Button::East),
+        PadInput::North => pad.is_pressed(Button::North),
+        PadInput::West => pad.is_pressed(Button::West),
+        PadInput::LeftTrigger => pad.is_pressed(Button::LeftTrigger),
+        PadInput::LeftTrigger2 => pad.is_pressed(Button::LeftTrigger2),
+        PadInput::RightTrigger => pad.is_pressed(Button::RightTrigger),
+        PadInput::RightTrigger2 => pad.is_pressed(Button::RightTrigger2),
+        PadInput::Select => pad.is_pressed(Button::Select),
+        PadInput::Start => pad.is_pressed(Button::Start),
+        PadInput::LeftThumb => pad.is_pressed(Button::LeftThumb),
+        PadInput::RightThumb => pad.is_pressed(Button::RightThumb),
+        PadInput::DPadUp => pad.is_pressed(Button::DPadUp),
+        PadInput::DPadDown => pad.is_pressed(Button::DPadDown),
+        PadInput::DPadLeft => pad.is_pressed(Button::DPadLeft),
+        PadInput::DPadRight => pad.is_pressed(Button::DPadRight),
+        // Up is positive on the Y axes
+        PadInput::LeftStickUp => axis(Axis::LeftStickY, 1.0),
+        PadInput::LeftStickDown => axis(Axis::LeftStickY, -1.0),
+        PadInput::LeftStickLeft => axis(Axis::LeftStickX, -1.0),
+        PadInput::LeftStickRight => axis(Axis::LeftStickX, 1.0),
+        PadInput::RightStickUp => axis(Axis::RightStickY, 1.0),
+        PadInput::RightStickDown => axis(Axis::RightStickY, -1.0),
+        PadInput::RightStickLeft => axis(Axis::RightStickX, -1.0),
+        PadInput::RightStickRight => axis(Axis::RightStickX, 1.0),
+    }
+}
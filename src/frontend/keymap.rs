@@ -0,0 +1,42 @@
+use winit::event::VirtualKeyCode;
+use winit_input_helper::WinitInputHelper;
+
+// Host keys bound to each of the 16 CHIP-8 keys
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Keymap {
+    pub keys: [Vec<VirtualKeyCode>; 16],
+}
+
+impl Keymap {
+    // 1234/QWER/ASDF/ZXCV for keys 0 to F
+    pub fn new() -> Self {
+        use VirtualKeyCode::*;
+        let layout = [Key1, Key2, Key3, Key4, Q, W, E, R, A, S, D, F, Z, X, C, V];
+        let mut keys: [Vec<VirtualKeyCode>; 16] = Default::default();
+        for (key, code) in keys.iter_mut().zip(layout.iter()) {
+            key.push(*code);
+        }
+        Self { keys }
+    }
+
+    // The held keys as a Chip8::set_keys mask, each CHIP-8 key is held while any of its
+    // host keys is
+    pub fn mask(&self, input: &WinitInputHelper) -> u16 {
+        let mut mask = 0;
+        for (i, codes) in self.keys.iter().enumerate() {
+            if codes
+                .iter()
+                .any(|code| input.key_pressed(*code) || input.key_held(*code))
+            {
+                mask |= 1 << i;
+            }
+        }
+        mask
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,6 @@
+// FNV-1a, a fast non-cryptographic hash used to identify roms and screens
+pub fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xCBF2_9CE4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0000_0100_0000_01B3)
+    })
+}
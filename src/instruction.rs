@@ -0,0 +1,261 @@
+use std::fmt;
+
+// A decoded instruction, x and y are register indices
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    // 00Cn
+    ScrollDown(u8),
+    // 00Dn (XO-CHIP)
+    ScrollUp(u8),
+    // 00E0
+    Cls,
+    // 00EE
+    Ret,
+    // 00FB
+    ScrollRight,
+    // 00FC
+    ScrollLeft,
+    // 00FD
+    Exit,
+    // 00FE
+    Lores,
+    // 00FF
+    Hires,
+    // 1nnn
+    Jp(u16),
+    // 2nnn
+    Call(u16),
+    // 3xkk
+    SeByte(u8, u8),
+    // 4xkk
+    SneByte(u8, u8),
+    // 5xy0
+    SeReg(u8, u8),
+    // 5xy2 (XO-CHIP)
+    SaveRange(u8, u8),
+    // 5xy3 (XO-CHIP)
+    LoadRange(u8, u8),
+    // 6xkk
+    LdByte(u8, u8),
+    // 7xkk
+    AddByte(u8, u8),
+    // 8xy0
+    LdReg(u8, u8),
+    // 8xy1
+    Or(u8, u8),
+    // 8xy2
+    And(u8, u8),
+    // 8xy3
+    Xor(u8, u8),
+    // 8xy4
+    Add(u8, u8),
+    // 8xy5
+    Sub(u8, u8),
+    // 8xy6
+    Shr(u8, u8),
+    // 8xy7
+    Subn(u8, u8),
+    // 8xyE
+    Shl(u8, u8),
+    // 9xy0
+    SneReg(u8, u8),
+    // Annn
+    LdI(u16),
+    // Bnnn, x is only used with the jump_vx quirk
+    JpV0(u8, u16),
+    // Cxkk
+    Rnd(u8, u8),
+    // Dxyn
+    Drw(u8, u8, u8),
+    // Ex9E
+    Skp(u8),
+    // ExA1
+    Sknp(u8),
+    // F000 nnnn (XO-CHIP)
+    LdILong(u16),
+    // Fn01 (XO-CHIP)
+    Plane(u8),
+    // F002 (XO-CHIP)
+    Audio,
+    // Fx07
+    LdVxDt(u8),
+    // Fx0A
+    LdVxK(u8),
+    // Fx15
+    LdDtVx(u8),
+    // Fx18
+    LdStVx(u8),
+    // Fx1E
+    AddIVx(u8),
+    // Fx29
+    LdFVx(u8),
+    // Fx30
+    LdHfVx(u8),
+    // Fx33
+    LdBVx(u8),
+    // Fx3A (XO-CHIP)
+    Pitch(u8),
+    // Fx55
+    LdIVx(u8),
+    // Fx65
+    LdVxI(u8),
+    // Fx75
+    LdRVx(u8),
+    // Fx85
+    LdVxR(u8),
+}
+
+impl Instruction {
+    // next is the word following the instruction, only read by F000 nnnn.
+    // The XO-CHIP opcodes are only decoded when xo_chip is set.
+    pub fn decode(instruction: u16, next: Option<u16>, xo_chip: bool) -> Option<Self> {
+        let opcode: u8 = ((instruction & 0xF000) >> 12) as u8;
+        let nnn: u16 = instruction & 0x0FFF;
+        let kk: u8 = (instruction & 0x00FF) as u8;
+        let n: u8 = (instruction & 0x000F) as u8;
+        let x: u8 = ((instruction & 0x0F00) >> 8) as u8;
+        let y: u8 = ((instruction & 0x00F0) >> 4) as u8;
+        let xo = xo_chip;
+
+        let decoded = match opcode {
+            0x0 => match kk {
+                0xC0..=0xCF => Instruction::ScrollDown(n),
+                0xD0..=0xDF if xo => Instruction::ScrollUp(n),
+                0xE0 => Instruction::Cls,
+                0xEE => Instruction::Ret,
+                0xFB => Instruction::ScrollRight,
+                0xFC => Instruction::ScrollLeft,
+                0xFD => Instruction::Exit,
+                0xFE => Instruction::Lores,
+                0xFF => Instruction::Hires,
+                _ => return None,
+            },
+            0x1 => Instruction::Jp(nnn),
+            0x2 => Instruction::Call(nnn),
+            0x3 => Instruction::SeByte(x, kk),
+            0x4 => Instruction::SneByte(x, kk),
+            0x5 => match n {
+                0x0 => Instruction::SeReg(x, y),
+                0x2 if xo => Instruction::SaveRange(x, y),
+                0x3 if xo => Instruction::LoadRange(x, y),
+                _ => return None,
+            },
+            0x6 => Instruction::LdByte(x, kk),
+            0x7 => Instruction::AddByte(x, kk),
+            0x8 => match n {
+                0x0 => Instruction::LdReg(x, y),
+                0x1 => Instruction::Or(x, y),
+                0x2 => Instruction::And(x, y),
+                0x3 => Instruction::Xor(x, y),
+                0x4 => Instruction::Add(x, y),
+                0x5 => Instruction::Sub(x, y),
+                0x6 => Instruction::Shr(x, y),
+                0x7 => Instruction::Subn(x, y),
+                0xE => Instruction::Shl(x, y),
+                _ => return None,
+            },
+            0x9 => match n {
+                0x0 => Instruction::SneReg(x, y),
+                _ => return None,
+            },
+            0xA => Instruction::LdI(nnn),
+            0xB => Instruction::JpV0(x, nnn),
+            0xC => Instruction::Rnd(x, kk),
+            0xD => Instruction::Drw(x, y, n),
+            0xE => match kk {
+                0x9E => Instruction::Skp(x),
+                0xA1 => Instruction::Sknp(x),
+                _ => return None,
+            },
+            0xF => match kk {
+                0x00 if xo && x == 0 => Instruction::LdILong(next?),
+                0x01 if xo => Instruction::Plane(x),
+                0x02 if xo && x == 0 => Instruction::Audio,
+                0x07 => Instruction::LdVxDt(x),
+                0x0A => Instruction::LdVxK(x),
+                0x15 => Instruction::LdDtVx(x),
+                0x18 => Instruction::LdStVx(x),
+                0x1E => Instruction::AddIVx(x),
+                0x29 => Instruction::LdFVx(x),
+                0x30 => Instruction::LdHfVx(x),
+                0x33 => Instruction::LdBVx(x),
+                0x3A if xo => Instruction::Pitch(x),
+                0x55 => Instruction::LdIVx(x),
+                0x65 => Instruction::LdVxI(x),
+                0x75 => Instruction::LdRVx(x),
+                0x85 => Instruction::LdVxR(x),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some(decoded)
+    }
+
+    // Size in bytes, F000 nnnn is the only four byte instruction
+    pub fn size(&self) -> u16 {
+        match self {
+            Instruction::LdILong(_) => 4,
+            _ => 2,
+        }
+    }
+}
+
+// Mnemonics follow Cowgod's Chip-8 Technical Reference
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Instruction::ScrollDown(n) => write!(f, "SCD {}", n),
+            Instruction::ScrollUp(n) => write!(f, "SCU {}", n),
+            Instruction::Cls => write!(f, "CLS"),
+            Instruction::Ret => write!(f, "RET"),
+            Instruction::ScrollRight => write!(f, "SCR"),
+            Instruction::ScrollLeft => write!(f, "SCL"),
+            Instruction::Exit => write!(f, "EXIT"),
+            Instruction::Lores => write!(f, "LOW"),
+            Instruction::Hires => write!(f, "HIGH"),
+            Instruction::Jp(nnn) => write!(f, "JP {:#05X}", nnn),
+            Instruction::Call(nnn) => write!(f, "CALL {:#05X}", nnn),
+            Instruction::SeByte(x, kk) => write!(f, "SE V{:X}, {:#04X}", x, kk),
+            Instruction::SneByte(x, kk) => write!(f, "SNE V{:X}, {:#04X}", x, kk),
+            Instruction::SeReg(x, y) => write!(f, "SE V{:X}, V{:X}", x, y),
+            Instruction::SaveRange(x, y) => write!(f, "SAVE V{:X}-V{:X}", x, y),
+            Instruction::LoadRange(x, y) => write!(f, "LOAD V{:X}-V{:X}", x, y),
+            Instruction::LdByte(x, kk) => write!(f, "LD V{:X}, {:#04X}", x, kk),
+            Instruction::AddByte(x, kk) => write!(f, "ADD V{:X}, {:#04X}", x, kk),
+            Instruction::LdReg(x, y) => write!(f, "LD V{:X}, V{:X}", x, y),
+            Instruction::Or(x, y) => write!(f, "OR V{:X}, V{:X}", x, y),
+            Instruction::And(x, y) => write!(f, "AND V{:X}, V{:X}", x, y),
+            Instruction::Xor(x, y) => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Instruction::Add(x, y) => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Instruction::Sub(x, y) => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Instruction::Shr(x, y) => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Instruction::Subn(x, y) => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Instruction::Shl(x, y) => write!(f, "SHL V{:X}, V{:X}", x, y),
+            Instruction::SneReg(x, y) => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Instruction::LdI(nnn) => write!(f, "LD I, {:#05X}", nnn),
+            Instruction::JpV0(0, nnn) => write!(f, "JP V0, {:#05X}", nnn),
+            // The high digit of nnn picks the register under the jump_vx quirk
+            Instruction::JpV0(x, nnn) => write!(f, "JP V0, {:#05X} (V{:X} with jump_vx)", nnn, x),
+            Instruction::Rnd(x, kk) => write!(f, "RND V{:X}, {:#04X}", x, kk),
+            Instruction::Drw(x, y, n) => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Instruction::Skp(x) => write!(f, "SKP V{:X}", x),
+            Instruction::Sknp(x) => write!(f, "SKNP V{:X}", x),
+            Instruction::LdILong(nnnn) => write!(f, "LD I, LONG {:#06X}", nnnn),
+            Instruction::Plane(n) => write!(f, "PLANE {}", n),
+            Instruction::Audio => write!(f, "AUDIO"),
+            Instruction::LdVxDt(x) => write!(f, "LD V{:X}, DT", x),
+            Instruction::LdVxK(x) => write!(f, "LD V{:X}, K", x),
+            Instruction::LdDtVx(x) => write!(f, "LD DT, V{:X}", x),
+            Instruction::LdStVx(x) => write!(f, "LD ST, V{:X}", x),
+            Instruction::AddIVx(x) => write!(f, "ADD I, V{:X}", x),
+            Instruction::LdFVx(x) => write!(f, "LD F, V{:X}", x),
+            Instruction::LdHfVx(x) => write!(f, "LD HF, V{:X}", x),
+            Instruction::LdBVx(x) => write!(f, "LD B, V{:X}", x),
+            Instruction::Pitch(x) => write!(f, "PITCH V{:X}", x),
+            Instruction::LdIVx(x) => write!(f, "LD [I], V{:X}", x),
+            Instruction::LdVxI(x) => write!(f, "LD V{:X}, [I]", x),
+            Instruction::LdRVx(x) => write!(f, "LD R, V{:X}", x),
+            Instruction::LdVxR(x) => write!(f, "LD V{:X}, R", x),
+        }
+    }
+}
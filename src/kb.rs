@@ -1,126 +1,84 @@
-use winit::event::VirtualKeyCode;
-use winit::event_loop::ControlFlow;
-use winit_input_helper::WinitInputHelper;
+use crate::state::{StateError, StateReader, StateWriter};
 
 pub struct Keyboard {
     pub keys: [bool; 16],
+    // Keys pressed and released since the last 60hz tick, as masks
+    pub pressed: u16,
+    pub released: u16,
+    // Fx0A destination register
     pub register: u8,
+    // Key pressed since Fx0A started waiting, the wait ends when it is released
+    pub wait_key: Option<u8>,
 }
 
 impl Keyboard {
     pub fn new() -> Self {
         Self {
             keys: [false; 16],
+            pressed: 0,
+            released: 0,
             register: 0,
+            wait_key: None,
         }
     }
 
-    pub fn handle_keyboard(
-        &mut self,
-        input: &mut WinitInputHelper,
-        control_flow: &mut ControlFlow,
-    ) {
-        if input.key_pressed(VirtualKeyCode::Key1) || input.key_held(VirtualKeyCode::Key1) {
-            self.keys[0] = true;
-        }
-        if input.key_pressed(VirtualKeyCode::Key2) || input.key_held(VirtualKeyCode::Key2) {
-            self.keys[1] = true;
-        }
-        if input.key_pressed(VirtualKeyCode::Key3) || input.key_held(VirtualKeyCode::Key3) {
-            self.keys[2] = true;
-        }
-        if input.key_pressed(VirtualKeyCode::Key4) || input.key_held(VirtualKeyCode::Key4) {
-            self.keys[3] = true;
-        }
-        if input.key_pressed(VirtualKeyCode::Q) || input.key_held(VirtualKeyCode::Q) {
-            self.keys[4] = true;
-        }
-        if input.key_pressed(VirtualKeyCode::W) || input.key_held(VirtualKeyCode::W) {
-            self.keys[5] = true;
-        }
-        if input.key_pressed(VirtualKeyCode::E) || input.key_held(VirtualKeyCode::E) {
-            self.keys[6] = true;
-        }
-        if input.key_pressed(VirtualKeyCode::R) || input.key_held(VirtualKeyCode::R) {
-            self.keys[7] = true;
-        }
-        if input.key_pressed(VirtualKeyCode::A) || input.key_held(VirtualKeyCode::A) {
-            self.keys[8] = true;
-        }
-        if input.key_pressed(VirtualKeyCode::S) || input.key_held(VirtualKeyCode::S) {
-            self.keys[9] = true;
-        }
-        if input.key_pressed(VirtualKeyCode::D) || input.key_held(VirtualKeyCode::D) {
-            self.keys[10] = true;
-        }
-        if input.key_pressed(VirtualKeyCode::F) || input.key_held(VirtualKeyCode::F) {
-            self.keys[11] = true;
-        }
-        if input.key_pressed(VirtualKeyCode::Z) || input.key_held(VirtualKeyCode::Z) {
-            self.keys[12] = true;
-        }
-        if input.key_pressed(VirtualKeyCode::X) || input.key_held(VirtualKeyCode::X) {
-            self.keys[13] = true;
-        }
-        if input.key_pressed(VirtualKeyCode::C) || input.key_held(VirtualKeyCode::C) {
-            self.keys[14] = true;
-        }
-        if input.key_pressed(VirtualKeyCode::V) || input.key_held(VirtualKeyCode::V) {
-            self.keys[15] = true;
+    // The held keys as a bitmask, key 0 in the lowest bit
+    pub fn mask(&self) -> u16 {
+        let mut mask: u16 = 0;
+        for (i, key) in self.keys.iter().enumerate() {
+            mask |= (*key as u16) << i;
         }
+        mask
+    }
 
-        if input.key_released(VirtualKeyCode::Key1) {
-            self.keys[0] = false;
-        }
-        if input.key_released(VirtualKeyCode::Key2) {
-            self.keys[1] = false;
-        }
-        if input.key_released(VirtualKeyCode::Key3) {
-            self.keys[2] = false;
-        }
-        if input.key_released(VirtualKeyCode::Key4) {
-            self.keys[3] = false;
-        }
-        if input.key_released(VirtualKeyCode::Q) {
-            self.keys[4] = false;
-        }
-        if input.key_released(VirtualKeyCode::W) {
-            self.keys[5] = false;
-        }
-        if input.key_released(VirtualKeyCode::E) {
-            self.keys[6] = false;
-        }
-        if input.key_released(VirtualKeyCode::R) {
-            self.keys[7] = false;
-        }
-        if input.key_released(VirtualKeyCode::A) {
-            self.keys[8] = false;
-        }
-        if input.key_released(VirtualKeyCode::S) {
-            self.keys[9] = false;
-        }
-        if input.key_released(VirtualKeyCode::D) {
-            self.keys[10] = false;
-        }
-        if input.key_released(VirtualKeyCode::F) {
-            self.keys[11] = false;
-        }
-        if input.key_released(VirtualKeyCode::Z) {
-            self.keys[12] = false;
-        }
-        if input.key_released(VirtualKeyCode::X) {
-            self.keys[13] = false;
-        }
-        if input.key_released(VirtualKeyCode::C) {
-            self.keys[14] = false;
-        }
-        if input.key_released(VirtualKeyCode::V) {
-            self.keys[15] = false;
-        }
+    // Returns the keys this pressed and released, as masks
+    pub fn set_mask(&mut self, mask: u16) -> (u16, u16) {
+        let old = self.mask();
+        let pressed = mask & !old;
+        let released = old & !mask;
+        self.pressed |= pressed;
+        self.released |= released;
+        for (i, key) in self.keys.iter_mut().enumerate() {
+            *key = mask & (1 << i) != 0;
+        }
+        (pressed, released)
+    }
 
-        if input.key_released(VirtualKeyCode::Escape) || input.quit() {
-            *control_flow = ControlFlow::Exit;
-            return;
-        }
+    // Called at every 60hz tick
+    pub fn end_frame(&mut self) {
+        self.pressed = 0;
+        self.released = 0;
+    }
+
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.u16(self.mask());
+        w.u16(self.pressed);
+        w.u16(self.released);
+        w.u8(self.register);
+        // 0xFF when no key is pressed yet
+        w.u8(self.wait_key.unwrap_or(0xFF));
+    }
+
+    pub fn read_state(r: &mut StateReader) -> Result<Self, StateError> {
+        let mut keyboard = Self::new();
+        keyboard.set_mask(r.u16()?);
+        keyboard.pressed = r.u16()?;
+        keyboard.released = r.u16()?;
+        keyboard.register = r.u8()?;
+        if keyboard.register > 0x0F {
+            return Err(StateError::Invalid("key register"));
+        }
+        keyboard.wait_key = match r.u8()? {
+            0xFF => None,
+            key if key <= 0x0F => Some(key),
+            _ => return Err(StateError::Invalid("wait key")),
+        };
+        Ok(keyboard)
+    }
+}
+
+impl Default for Keyboard {
+    fn default() -> Self {
+        Self::new()
     }
 }
@@ -0,0 +1,28 @@
+pub mod assembler;
+pub mod audio;
+#[cfg(feature = "audio")]
+pub mod audio_device;
+pub mod bus;
+pub mod chip8;
+pub mod cpu;
+pub mod debugger;
+pub mod disasm;
+pub mod display;
+pub mod error;
+pub mod hash;
+pub mod instruction;
+pub mod kb;
+pub mod movie;
+pub mod palette;
+pub mod phosphor;
+pub mod quirks;
+pub mod ram;
+pub mod rewind;
+pub mod rng;
+pub mod screenshot;
+pub mod state;
+
+pub use crate::chip8::Chip8;
+pub use crate::error::Chip8Error;
+pub use crate::quirks::Quirks;
+pub use crate::state::StateError;
@@ -1,96 +1,291 @@
 use std::env;
-use std::fs::File;
-use std::io::Read;
+use std::fs;
 
-use pixels::{Pixels, SurfaceTexture};
-use winit::dpi::LogicalSize;
-use winit::event::Event;
-use winit::event_loop::{ControlFlow, EventLoop};
-use winit::window::WindowBuilder;
-use winit_input_helper::WinitInputHelper;
+use chip8::assembler;
+use chip8::audio::WavSink;
+use chip8::disasm;
+use chip8::movie::{KeyChange, Movie};
+use chip8::palette::{self, Palette};
+use chip8::{Chip8, Quirks};
 
-use chip8::Chip8;
+// The emulator window, without it the binary only has the headless commands
+#[cfg(feature = "frontend")]
+mod frontend;
 
-mod bus;
-mod chip8;
-mod cpu;
-mod display;
-mod kb;
-mod ram;
+// Sample rate of WAV recordings made without a sound device
+const WAV_SAMPLE_RATE: u32 = 44100;
 
 fn main() {
-    let filename = env::args().nth(1).unwrap_or("games/INVADERS".to_string());
-    let mut font_data = Vec::<u8>::new();
-    let mut game_data = Vec::<u8>::new();
+    match env::args().nth(1).as_deref() {
+        Some("disasm") => return disasm_command(env::args().skip(2)),
+        Some("assemble") => return assemble_command(env::args().skip(2)),
+        Some("run") => return run_command(env::args().skip(2)),
+        _ => {}
+    }
 
-    let mut font_file = File::open("FONTS.chip8").unwrap();
-    let mut game_file = File::open(filename).unwrap();
+    #[cfg(feature = "frontend")]
+    frontend::run(env::args().skip(1));
+    #[cfg(not(feature = "frontend"))]
+    {
+        eprintln!(
+            "Usage: chip8 run|disasm|assemble ..., built without the frontend feature so there is no emulator window"
+        );
+        std::process::exit(1);
+    }
+}
 
-    font_file.read_to_end(&mut font_data).unwrap();
-    game_file.read_to_end(&mut game_data).unwrap();
+// Exits with the list of palettes when there is none by that name
+fn find_palette(palettes: &[Palette], name: &str) -> usize {
+    palettes
+        .iter()
+        .position(|palette| palette.name == name)
+        .unwrap_or_else(|| {
+            let names: Vec<&str> = palettes.iter().map(|p| p.name.as_str()).collect();
+            eprintln!("Unknown palette '{}', expected {}", name, names.join(", "));
+            std::process::exit(1);
+        })
+}
 
-    let mut chip8 = Chip8::new();
-    chip8.load_mem(&font_data, 0x0);
-    chip8.load_mem(&game_data, 0x200);
+fn parse_quirks(name: &str) -> Quirks {
+    Quirks::from_name(name).unwrap_or_else(|| {
+        eprintln!(
+            "Unknown quirks profile '{}', expected vip, chip48, schip, modern or xo-chip",
+            name
+        );
+        std::process::exit(1);
+    })
+}
 
-    let mut input = WinitInputHelper::new();
-    let event_loop = EventLoop::new();
-    let window = WindowBuilder::new()
-        .with_title("Chip-8 Emulator")
-        .with_inner_size(LogicalSize::new(640, 320))
-        .with_resizable(false)
-        .build(&event_loop)
-        .unwrap();
+// chip8 disasm <rom> [--quirks <name>], prints the listing to stdout
+fn disasm_command(mut args: impl Iterator<Item = String>) {
+    let mut filename = None;
+    let mut quirks = Quirks::default();
+    let mut octo = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--quirks" => quirks = parse_quirks(&args.next().unwrap_or_default()),
+            "--octo" => octo = true,
+            _ => filename = Some(arg),
+        }
+    }
 
-    let window_size = window.inner_size();
-    let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, &window);
+    let filename = filename.unwrap_or_else(|| {
+        eprintln!("Usage: chip8 disasm <rom> [--quirks <name>] [--octo]");
+        std::process::exit(1);
+    });
+    let rom = fs::read(&filename).unwrap_or_else(|e| {
+        eprintln!("Could not read {}: {}", filename, e);
+        std::process::exit(1);
+    });
+    if octo {
+        print!("{}", disasm::to_octo(&rom, quirks.xo_chip));
+    } else {
+        print!("{}", disasm::disassemble(&rom, quirks.xo_chip));
+    }
+}
 
-    let mut pixels = Pixels::new(640, 320, surface_texture).unwrap();
+// chip8 run <rom> [options], runs without a window and dumps the final screen.
+// Exits with 1 on an emulation error or when the screen hash doesn't match --expect.
+fn run_command(mut args: impl Iterator<Item = String>) {
+    let mut filename = None;
+    let mut quirks = Quirks::default();
+    let mut frames = None;
+    let mut seed = 0;
+    let mut script = None;
+    let mut play_file = None;
+    let mut dump = "text".to_string();
+    let mut output = None;
+    let mut expect = None;
+    let mut wav_file = None;
+    let mut palette_name = "classic".to_string();
+    let mut scale = None;
+    let number = |name: &str, value: Option<String>| -> u64 {
+        let value = value.unwrap_or_default();
+        value.parse().unwrap_or_else(|_| {
+            eprintln!("Invalid {} '{}', expected a number", name, value);
+            std::process::exit(1);
+        })
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--quirks" => quirks = parse_quirks(&args.next().unwrap_or_default()),
+            "--frames" => frames = Some(number("frame count", args.next())),
+            "--seed" => seed = number("seed", args.next()),
+            "--keys" => script = args.next(),
+            "--play" => play_file = args.next(),
+            "--dump" => dump = args.next().unwrap_or_default(),
+            "-o" => output = args.next(),
+            "--expect" => expect = args.next(),
+            "--wav" => wav_file = args.next(),
+            "--palette" => palette_name = args.next().unwrap_or_default(),
+            "--scale" => scale = Some(number("scale", args.next()) as usize),
+            _ => filename = Some(arg),
+        }
+    }
 
-    event_loop.run(move |event, _, control_flow| {
-        if input.update(&event) {
-            chip8.bus.keyboard.handle_keyboard(&mut input, control_flow);
+    let filename = filename.unwrap_or_else(|| {
+        eprintln!(
+            "Usage: chip8 run <rom> [--frames <n>] [--quirks <name>] [--seed <n>] \
+             [--keys <frame:keys,...>] [--play <movie>] [--dump text|png|hash] [-o <file>] [--expect <hash>] \
+             [--wav <file>] [--palette <name>] [--scale <n>]"
+        );
+        std::process::exit(1);
+    });
+    let rom = fs::read(&filename).unwrap_or_else(|e| {
+        eprintln!("Could not read {}: {}", filename, e);
+        std::process::exit(1);
+    });
+
+    if play_file.is_some() && script.is_some() {
+        eprintln!("--keys can't be used with --play, the movie has its own keys");
+        std::process::exit(1);
+    }
+
+    // Scripted keys are played back as a movie
+    let mut movie = match play_file {
+        Some(path) => {
+            let movie = fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|text| {
+                    Movie::parse(&text)
+                        .and_then(|movie| movie.check_rom(&rom).map(|_| movie))
+                        .map_err(|e| e.to_string())
+                })
+                .unwrap_or_else(|e| {
+                    eprintln!("Could not load movie {}: {}", path, e);
+                    std::process::exit(1);
+                });
+            movie
         }
+        None => Movie::new(&rom, seed, quirks),
+    };
+    if let Some(script) = script {
+        movie.changes = parse_key_script(&script).unwrap_or_else(|e| {
+            eprintln!("Invalid key script: {}", e);
+            std::process::exit(1);
+        });
+    }
+    movie.length = frames.unwrap_or(if movie.length > 0 { movie.length } else { 600 });
 
-        if let Event::RedrawRequested(_) = event {
-            draw(pixels.get_frame(), &chip8.bus.display.framebuffer);
-            if pixels.render().is_err() {
-                *control_flow = ControlFlow::Exit;
-                return;
-            }
+    let mut chip8 = Chip8::new(movie.quirks);
+    if wav_file.is_some() {
+        chip8.audio.sample_rate = WAV_SAMPLE_RATE;
+    }
+    let result = chip8.load_rom(&rom).and_then(|_| movie.play(&mut chip8));
+    if let Err(e) = result {
+        eprintln!("{}: {} (frame {})", filename, e, chip8.frame);
+        std::process::exit(1);
+    }
+
+    if let Some(path) = wav_file {
+        let written = WavSink::create(&path, WAV_SAMPLE_RATE).and_then(|mut sink| {
+            chip8.audio.flush(&mut sink);
+            sink.finish()
+        });
+        if let Err(e) = written {
+            eprintln!("Could not write {}: {}", path, e);
+            std::process::exit(1);
         }
+    }
 
-        // Block cycle execution if waiting for input
-        if chip8.cpu.wait_for_input {
-            for (i, key) in chip8.bus.keyboard.keys.iter().enumerate() {
-                if *key {
-                    chip8.cpu.reg[chip8.bus.keyboard.register as usize] = i as u8;
-                    chip8.cpu.wait_for_input = false;
-                    chip8.cpu.instr_timer = std::time::Instant::now();
-                }
+    let palettes = palette::builtin();
+    let colors = palettes[find_palette(&palettes, &palette_name)].colors;
+    let display = &chip8.bus.display;
+    let hash = format!("{:016x}", display.hash());
+    match dump.as_str() {
+        "text" => print!("{}", display.to_text()),
+        "hash" => println!("{}", hash),
+        "png" => {
+            let path = output.unwrap_or_else(|| format!("{}.png", filename));
+            let written = fs::File::create(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|file| {
+                    display
+                        .write_png(
+                            scale.unwrap_or(640 / display.width()),
+                            &colors,
+                            std::io::BufWriter::new(file),
+                        )
+                        .map_err(|e| e.to_string())
+                });
+            if let Err(e) = written {
+                eprintln!("Could not write {}: {}", path, e);
+                std::process::exit(1);
             }
-        } else {
-            chip8.exec_cycle();
         }
+        _ => {
+            eprintln!("Unknown dump format '{}', expected text, png or hash", dump);
+            std::process::exit(1);
+        }
+    }
 
-        if chip8.bus.display.req_draw {
-            window.request_redraw();
-            chip8.bus.display.req_draw = false;
+    if let Some(expected) = expect {
+        if expected.trim_start_matches("0x") != hash {
+            eprintln!(
+                "{}: screen hash {} does not match {}",
+                filename, hash, expected
+            );
+            std::process::exit(1);
         }
-    });
+    }
 }
 
-fn draw(frame: &mut [u8], buffer: &[[bool; 64]; 32]) {
-    for (i, pixel) in frame.chunks_exact_mut(4).enumerate() {
-        let x = i % 640 as usize;
-        let y = i / 640 as usize;
+// "30:5,45:,90:4a" holds key 5 from frame 30, releases everything at 45, then holds 4 and A
+fn parse_key_script(script: &str) -> Result<Vec<KeyChange>, String> {
+    let mut changes: Vec<KeyChange> = Vec::new();
+    for entry in script.split(',').filter(|e| !e.is_empty()) {
+        let (frame, keys) = entry
+            .split_once(':')
+            .ok_or_else(|| format!("expected frame:keys, found '{}'", entry))?;
+        let frame = frame
+            .parse()
+            .map_err(|_| format!("invalid frame '{}'", frame))?;
+        let mut mask = 0;
+        for key in keys.chars() {
+            let key = key
+                .to_digit(16)
+                .ok_or_else(|| format!("invalid key '{}'", key))?;
+            mask |= 1 << key;
+        }
+        if matches!(changes.last(), Some(last) if last.frame >= frame) {
+            return Err("frames must be increasing".to_string());
+        }
+        changes.push(KeyChange { frame, keys: mask });
+    }
+    Ok(changes)
+}
 
-        let rgba = if buffer[y / 10][x / 10] == true {
-            [0xFF, 0xFF, 0xFF, 0xFF]
-        } else {
-            [0x0, 0x0, 0x0, 0xFF]
-        };
+// chip8 assemble <source> [-o <rom>], the rom defaults to the source with a .ch8 extension
+fn assemble_command(mut args: impl Iterator<Item = String>) {
+    let mut filename = None;
+    let mut output = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" => output = args.next(),
+            _ => filename = Some(arg),
+        }
+    }
 
-        pixel.copy_from_slice(&rgba);
+    let filename = filename.unwrap_or_else(|| {
+        eprintln!("Usage: chip8 assemble <source> [-o <rom>]");
+        std::process::exit(1);
+    });
+    let output = output.unwrap_or_else(|| {
+        std::path::Path::new(&filename)
+            .with_extension("ch8")
+            .to_string_lossy()
+            .into_owned()
+    });
+    let source = fs::read_to_string(&filename).unwrap_or_else(|e| {
+        eprintln!("Could not read {}: {}", filename, e);
+        std::process::exit(1);
+    });
+    let rom = assembler::assemble(&source).unwrap_or_else(|e| {
+        eprintln!("{}: {}", filename, e);
+        std::process::exit(1);
+    });
+    if let Err(e) = fs::write(&output, &rom) {
+        eprintln!("Could not write {}: {}", output, e);
+        std::process::exit(1);
     }
 }
@@ -0,0 +1,189 @@
+use std::fmt;
+
+use crate::chip8::Chip8;
+use crate::error::Chip8Error;
+use crate::hash::fnv1a;
+use crate::quirks::Quirks;
+
+// Movies are text so they can be attached to bug reports and diffed:
+//
+// chip8-movie 1
+// rom 0x9c1b0e3dd4c1b0a5
+// seed 1234
+// quirks 0x00
+// length 3600
+// 120 0x0020
+// 135 0x0000
+//
+// Every line after the header is a frame number and the keys held from that frame on.
+const HEADER: &str = "chip8-movie 1";
+
+// Identifies the rom a movie was recorded with
+pub fn rom_hash(rom: &[u8]) -> u64 {
+    fnv1a(rom)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyChange {
+    pub frame: u64,
+    // Keyboard::mask of the held keys
+    pub keys: u16,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MovieError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for MovieError {}
+
+// Key input of a session from power on, keyed by the frame it was applied at.
+// Keys only change at frame boundaries so playback hits the same instruction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Movie {
+    pub rom_hash: u64,
+    pub seed: u64,
+    pub quirks: Quirks,
+    pub changes: Vec<KeyChange>,
+    // Frame the recording ended at
+    pub length: u64,
+}
+
+impl Movie {
+    pub fn new(rom: &[u8], seed: u64, quirks: Quirks) -> Self {
+        Self {
+            rom_hash: rom_hash(rom),
+            seed,
+            quirks,
+            changes: Vec::new(),
+            length: 0,
+        }
+    }
+
+    // Call at every frame boundary with the keys applied to the machine
+    pub fn record(&mut self, frame: u64, keys: u16) {
+        if keys != self.keys_at(frame) {
+            self.changes.push(KeyChange { frame, keys });
+        }
+        self.length = self.length.max(frame);
+    }
+
+    // Drops everything after frame, used when the machine is rewound while recording
+    pub fn truncate(&mut self, frame: u64) {
+        self.changes.retain(|c| c.frame <= frame);
+        self.length = frame;
+    }
+
+    // Keys held at frame
+    pub fn keys_at(&self, frame: u64) -> u16 {
+        match self.changes.partition_point(|c| c.frame <= frame) {
+            0 => 0,
+            i => self.changes[i - 1].keys,
+        }
+    }
+
+    // Plays the whole movie on a machine that was just loaded with its rom
+    pub fn play(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        chip8.seed(self.seed);
+        while chip8.frame < self.length {
+            chip8.set_keys(self.keys_at(chip8.frame));
+            chip8.run_frame()?;
+        }
+        Ok(())
+    }
+
+    // A movie only replays on the rom it was recorded with, reported on the rom line
+    pub fn check_rom(&self, rom: &[u8]) -> Result<(), MovieError> {
+        if self.rom_hash != rom_hash(rom) {
+            return Err(error(2, "recorded with a different rom".to_string()));
+        }
+        Ok(())
+    }
+
+    pub fn parse(text: &str) -> Result<Self, MovieError> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()));
+        match lines.next() {
+            Some((_, HEADER)) => {}
+            _ => return Err(error(1, format!("expected '{}'", HEADER))),
+        }
+
+        let mut field = |name: &str| -> Result<u64, MovieError> {
+            let (n, line) = lines.next().unwrap_or((0, ""));
+            let mut words = line.split_whitespace();
+            match (words.next(), words.next().and_then(parse_number)) {
+                (Some(word), Some(value)) if word == name => Ok(value),
+                _ => Err(error(n, format!("expected {}", name))),
+            }
+        };
+        let rom_hash = field("rom")?;
+        let seed = field("seed")?;
+        let quirks = field("quirks")?;
+        if quirks > 0x7F {
+            // The quirks are on line 4, after the header, rom and seed
+            let message = format!("quirks {:#x} out of range, expected 0x00 to 0x7f", quirks);
+            return Err(error(4, message));
+        }
+        let quirks = Quirks::from_bits(quirks as u8);
+        let length = field("length")?;
+
+        let mut changes: Vec<KeyChange> = Vec::new();
+        for (n, line) in lines.filter(|(_, line)| !line.is_empty()) {
+            let mut words = line.split_whitespace().map(parse_number);
+            let change = match (words.next(), words.next()) {
+                (Some(Some(frame)), Some(Some(keys))) if keys <= 0xFFFF => KeyChange {
+                    frame,
+                    keys: keys as u16,
+                },
+                _ => return Err(error(n, "expected a frame and a key mask".to_string())),
+            };
+            if matches!(changes.last(), Some(last) if last.frame >= change.frame) {
+                return Err(error(n, "frames must be increasing".to_string()));
+            }
+            changes.push(change);
+        }
+
+        Ok(Self {
+            rom_hash,
+            seed,
+            quirks,
+            changes,
+            length,
+        })
+    }
+}
+
+impl fmt::Display for Movie {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        writeln!(f, "rom {:#018x}", self.rom_hash)?;
+        writeln!(f, "seed {}", self.seed)?;
+        writeln!(f, "quirks {:#04x}", self.quirks.bits())?;
+        writeln!(f, "length {}", self.length)?;
+        for change in &self.changes {
+            writeln!(f, "{} {:#06x}", change.frame, change.keys)?;
+        }
+        Ok(())
+    }
+}
+
+// Decimal, or hex with a 0x prefix
+fn parse_number(text: &str) -> Option<u64> {
+    match text.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+fn error(line: usize, message: String) -> MovieError {
+    MovieError { line, message }
+}
@@ -0,0 +1,75 @@
+// RGBA for the background, plane 1, plane 2 and both planes.
+// Monochrome roms only use the first two.
+pub type Colors = [[u8; 4]; 4];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Palette {
+    pub name: String,
+    pub colors: Colors,
+}
+
+impl Palette {
+    // Two hex colors give the background and foreground, planes 2 and both then use the
+    // foreground too. Four colors set every plane combination.
+    pub fn parse(name: &str, colors: &[&str]) -> Result<Self, String> {
+        if colors.len() != 2 && colors.len() != 4 {
+            return Err(format!(
+                "palette {} has {} colors, expected 2 or 4",
+                name,
+                colors.len()
+            ));
+        }
+        let mut parsed = Vec::new();
+        for color in colors {
+            parsed.push(
+                parse_color(color)
+                    .ok_or_else(|| format!("palette {} has an invalid color '{}'", name, color))?,
+            );
+        }
+        let foreground = parsed[1];
+        parsed.resize(4, foreground);
+        let mut palette = Self {
+            name: name.to_string(),
+            colors: [[0; 4]; 4],
+        };
+        palette.colors.copy_from_slice(&parsed);
+        Ok(palette)
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        builtin().remove(0)
+    }
+}
+
+// "#RRGGBB", the # is optional
+pub fn parse_color(text: &str) -> Option<[u8; 4]> {
+    let hex = text.strip_prefix('#').unwrap_or(text);
+    // from_str_radix would also take a leading sign
+    if hex.len() != 6 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    Some([(value >> 16) as u8, (value >> 8) as u8, value as u8, 0xFF])
+}
+
+// The palettes every build has, classic first
+pub fn builtin() -> Vec<Palette> {
+    let palettes: [(&str, [&str; 4]); 6] = [
+        ("classic", ["#000000", "#FFFFFF", "#AAAAAA", "#555555"]),
+        ("amber", ["#1A1000", "#FFB000", "#7F5800", "#FFD780"]),
+        ("green", ["#001A00", "#33FF33", "#198019", "#99FF99"]),
+        ("lcd", ["#9BBC0F", "#0F380F", "#8BAC0F", "#306230"]),
+        (
+            "high-contrast",
+            ["#000000", "#FFFFFF", "#FFFF00", "#00FFFF"],
+        ),
+        // Okabe-Ito blue and orange stay apart for every kind of color blindness
+        ("colorblind", ["#000000", "#FFFFFF", "#0072B2", "#E69F00"]),
+    ];
+    palettes
+        .iter()
+        .map(|(name, colors)| Palette::parse(name, colors).unwrap())
+        .collect()
+}
@@ -0,0 +1,74 @@
+use crate::display::{Display, HEIGHT, WIDTH};
+use crate::palette::Colors;
+
+// Persistence of vision for flickering games. Sampled once per frame, a lit pixel shows its
+// color at once and a pixel that goes dark fades to the background, keeping decay of its
+// brightness each frame. Only the picture is blended, the emulated display is untouched.
+pub struct Phosphor {
+    // 0 shows every frame as it is, values close to 1 fade slowly
+    pub decay: f32,
+    glow: Vec<[f32; 4]>,
+    hires: bool,
+}
+
+impl Phosphor {
+    pub fn new(decay: f32) -> Self {
+        Self {
+            decay: decay.clamp(0.0, 1.0),
+            glow: vec![[0.0; 4]; WIDTH * HEIGHT],
+            hires: false,
+        }
+    }
+
+    // Drops the history, the next picture is the display as it is
+    pub fn reset(&mut self, display: &Display, colors: &Colors) {
+        self.hires = display.hires;
+        for y in 0..display.height() {
+            for x in 0..display.width() {
+                self.glow[y * WIDTH + x] = to_float(colors[color(display, x, y)]);
+            }
+        }
+    }
+
+    // Blends in the display at the end of a frame
+    pub fn update(&mut self, display: &Display, colors: &Colors) {
+        if display.hires != self.hires {
+            return self.reset(display, colors);
+        }
+        for y in 0..display.height() {
+            for x in 0..display.width() {
+                let index = color(display, x, y);
+                let target = to_float(colors[index]);
+                let glow = &mut self.glow[y * WIDTH + x];
+                if index != 0 {
+                    *glow = target;
+                } else {
+                    for (channel, target) in glow.iter_mut().zip(target.iter()) {
+                        *channel = *channel * self.decay + target * (1.0 - self.decay);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 4] {
+        let glow = self.glow[y * WIDTH + x];
+        let mut rgba = [0; 4];
+        for (byte, channel) in rgba.iter_mut().zip(glow.iter()) {
+            *byte = channel.round() as u8;
+        }
+        rgba
+    }
+}
+
+fn color(display: &Display, x: usize, y: usize) -> usize {
+    (display.framebuffer[y][x] & 3) as usize
+}
+
+fn to_float(color: [u8; 4]) -> [f32; 4] {
+    let mut float = [0.0; 4];
+    for (channel, byte) in float.iter_mut().zip(color.iter()) {
+        *channel = *byte as f32;
+    }
+    float
+}
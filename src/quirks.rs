@@ -0,0 +1,134 @@
+use crate::state::{StateError, StateReader, StateWriter};
+
+// Behaviour of the opcodes that differ between CHIP-8 interpreters.
+// The default is the behaviour the emulator had before quirks were configurable.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Quirks {
+    // 8xy6/8xyE shift Vy into Vx instead of shifting Vx in place
+    pub shift_vy: bool,
+    // Fx55/Fx65 leave I pointing past the last register transferred
+    pub load_store_inc_i: bool,
+    // Bnnn jumps to nnn + Vx (Bxnn) instead of nnn + V0
+    pub jump_vx: bool,
+    // 8xy1/8xy2/8xy3 reset VF to 0
+    pub vf_reset: bool,
+    // Sprites are clipped at the screen edges instead of wrapping around
+    pub clip_sprites: bool,
+    // Dxyn waits for the next 60hz tick before drawing
+    pub display_wait: bool,
+    // XO-CHIP extensions: 64 KiB of memory, F000 nnnn, 5xy2/5xy3, Fn01 and 00Dn
+    pub xo_chip: bool,
+}
+
+impl Quirks {
+    pub fn cosmac_vip() -> Self {
+        Self {
+            shift_vy: true,
+            load_store_inc_i: true,
+            jump_vx: false,
+            vf_reset: true,
+            clip_sprites: true,
+            display_wait: true,
+            xo_chip: false,
+        }
+    }
+
+    pub fn chip48() -> Self {
+        Self {
+            shift_vy: false,
+            load_store_inc_i: true,
+            jump_vx: true,
+            vf_reset: false,
+            clip_sprites: true,
+            display_wait: false,
+            xo_chip: false,
+        }
+    }
+
+    pub fn superchip() -> Self {
+        Self {
+            shift_vy: false,
+            load_store_inc_i: false,
+            jump_vx: true,
+            vf_reset: false,
+            clip_sprites: true,
+            display_wait: false,
+            xo_chip: false,
+        }
+    }
+
+    pub fn modern() -> Self {
+        Self {
+            shift_vy: true,
+            load_store_inc_i: true,
+            jump_vx: false,
+            vf_reset: false,
+            clip_sprites: false,
+            display_wait: false,
+            xo_chip: false,
+        }
+    }
+
+    pub fn xo_chip() -> Self {
+        Self {
+            xo_chip: true,
+            ..Self::modern()
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "vip" | "cosmac" | "cosmac-vip" => Some(Self::cosmac_vip()),
+            "chip48" | "chip-48" => Some(Self::chip48()),
+            "schip" | "superchip" | "super-chip" => Some(Self::superchip()),
+            "modern" | "octo" => Some(Self::modern()),
+            "xo" | "xochip" | "xo-chip" => Some(Self::xo_chip()),
+            _ => None,
+        }
+    }
+
+    // One bit per quirk in declaration order, shift_vy in the lowest bit
+    pub fn bits(&self) -> u8 {
+        self.shift_vy as u8
+            | (self.load_store_inc_i as u8) << 1
+            | (self.jump_vx as u8) << 2
+            | (self.vf_reset as u8) << 3
+            | (self.clip_sprites as u8) << 4
+            | (self.display_wait as u8) << 5
+            | (self.xo_chip as u8) << 6
+    }
+
+    pub fn from_bits(bits: u8) -> Self {
+        Self {
+            shift_vy: bits & 1 != 0,
+            load_store_inc_i: bits & 1 << 1 != 0,
+            jump_vx: bits & 1 << 2 != 0,
+            vf_reset: bits & 1 << 3 != 0,
+            clip_sprites: bits & 1 << 4 != 0,
+            display_wait: bits & 1 << 5 != 0,
+            xo_chip: bits & 1 << 6 != 0,
+        }
+    }
+
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.bool(self.shift_vy);
+        w.bool(self.load_store_inc_i);
+        w.bool(self.jump_vx);
+        w.bool(self.vf_reset);
+        w.bool(self.clip_sprites);
+        w.bool(self.display_wait);
+        w.bool(self.xo_chip);
+    }
+
+    pub fn read_state(r: &mut StateReader) -> Result<Self, StateError> {
+        Ok(Self {
+            shift_vy: r.bool()?,
+            load_store_inc_i: r.bool()?,
+            jump_vx: r.bool()?,
+            vf_reset: r.bool()?,
+            clip_sprites: r.bool()?,
+            display_wait: r.bool()?,
+            xo_chip: r.bool()?,
+        })
+    }
+}
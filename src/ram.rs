@@ -1,17 +1,75 @@
+use std::cell::RefCell;
+
+use crate::error::Fault;
+use crate::state::{StateError, StateReader, StateWriter};
+
+// A read or write made through read_bytes/write_bytes while tracing
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Access {
+    pub address: usize,
+    pub len: usize,
+    pub write: bool,
+}
+
 pub struct Ram {
-    pub mem: [u8; 4096],
+    pub mem: Vec<u8>,
+    // Record every access in accesses, used by the debugger watchpoints
+    pub trace: bool,
+    pub accesses: RefCell<Vec<Access>>,
 }
 
 impl Ram {
-    pub fn new() -> Self {
-        Self { mem: [0; 4096] }
+    // 4096 bytes for CHIP-8/SCHIP, 65536 bytes for XO-CHIP
+    pub fn new(size: usize) -> Self {
+        Self {
+            mem: vec![0; size],
+            trace: false,
+            accesses: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.u32(self.mem.len() as u32);
+        w.bytes(&self.mem);
+    }
+
+    pub fn read_state(r: &mut StateReader) -> Result<Self, StateError> {
+        let size = r.u32()? as usize;
+        if size != 0x1000 && size != 0x10000 {
+            return Err(StateError::Invalid("memory size"));
+        }
+        let mut ram = Self::new(0);
+        ram.mem = r.bytes(size)?.to_vec();
+        Ok(ram)
     }
 
     pub fn write_byte(&mut self, address: usize, value: u8) {
         self.mem[address] = value;
     }
 
-    pub fn read_byte(&self, address: usize) -> u8 {
-        self.mem[address]
+    pub fn read_bytes(&self, address: usize, len: usize) -> Result<&[u8], Fault> {
+        self.record(address, len, false);
+        self.mem
+            .get(address..address + len)
+            .ok_or_else(|| Fault::MemoryOutOfBounds(address.max(self.mem.len())))
+    }
+
+    pub fn write_bytes(&mut self, address: usize, bytes: &[u8]) -> Result<(), Fault> {
+        self.record(address, bytes.len(), true);
+        let len = self.mem.len();
+        self.mem
+            .get_mut(address..address + bytes.len())
+            .ok_or(Fault::MemoryOutOfBounds(address.max(len)))?
+            .copy_from_slice(bytes);
+        Ok(())
+    }
+    fn record(&self, address: usize, len: usize, write: bool) {
+        if self.trace {
+            self.accesses.borrow_mut().push(Access {
+                address,
+                len,
+                write,
+            });
+        }
     }
 }
@@ -0,0 +1,124 @@
+use std::collections::VecDeque;
+
+// Ring buffer of per-frame save states.
+// The newest state is kept whole, every older state is stored as the
+// run-length encoded XOR against the state recorded after it, so dropping
+// the oldest entries never invalidates the others.
+pub struct Rewind {
+    capacity: usize,
+    head: Option<Vec<u8>>,
+    entries: VecDeque<Entry>,
+}
+
+enum Entry {
+    Delta(Vec<u8>),
+    // Used when the state size changed between frames
+    Full(Vec<u8>),
+}
+
+impl Rewind {
+    // capacity is the number of frames that can be stepped back
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            head: None,
+            entries: VecDeque::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.head = None;
+        self.entries.clear();
+    }
+
+    pub fn push(&mut self, state: Vec<u8>) {
+        if let Some(head) = self.head.take() {
+            let entry = if head.len() == state.len() {
+                Entry::Delta(encode(&xor(&head, &state)))
+            } else {
+                Entry::Full(head)
+            };
+            self.entries.push_back(entry);
+            while self.entries.len() > self.capacity {
+                self.entries.pop_front();
+            }
+        }
+        self.head = Some(state);
+    }
+
+    // Returns the state recorded one frame before the newest one
+    pub fn pop(&mut self) -> Option<Vec<u8>> {
+        let entry = self.entries.pop_back()?;
+        let head = self.head.take()?;
+        let previous = match entry {
+            Entry::Delta(delta) => xor(&head, &decode(&delta, head.len())),
+            Entry::Full(state) => state,
+        };
+        self.head = Some(previous.clone());
+        Some(previous)
+    }
+}
+
+fn xor(a: &[u8], b: &[u8]) -> Vec<u8> {
+    a.iter().zip(b.iter()).map(|(a, b)| a ^ b).collect()
+}
+
+// Alternating runs: a varint count of zero bytes, a varint count of literal bytes, the literals
+fn encode(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let zeros = data[pos..].iter().take_while(|b| **b == 0).count();
+        pos += zeros;
+        let literals = data[pos..].iter().take_while(|b| **b != 0).count();
+        write_varint(&mut out, zeros);
+        write_varint(&mut out, literals);
+        out.extend_from_slice(&data[pos..pos + literals]);
+        pos += literals;
+    }
+    out
+}
+
+fn decode(data: &[u8], len: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(len);
+    let mut pos = 0;
+    while pos < data.len() {
+        let zeros = read_varint(data, &mut pos);
+        let literals = read_varint(data, &mut pos);
+        out.resize(out.len() + zeros, 0);
+        out.extend_from_slice(&data[pos..pos + literals]);
+        pos += literals;
+    }
+    out.resize(len, 0);
+    out
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7F) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = data[*pos];
+        *pos += 1;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return value;
+        }
+        shift += 7;
+    }
+}
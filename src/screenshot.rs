@@ -0,0 +1,85 @@
+use std::fs;
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::display::Display;
+use crate::palette::Colors;
+
+// Writes the display to dir as <prefix>-YYYYMMDD-HHMMSS-mmm.png (UTC), creating dir when
+// needed. A scale of 1 keeps the native 64x32 or 128x64 resolution. An existing file is
+// never replaced, the name gets a -1, -2, ... suffix instead.
+pub fn save(
+    display: &Display,
+    scale: usize,
+    colors: &Colors,
+    dir: &Path,
+    prefix: &str,
+) -> Result<PathBuf, png::EncodingError> {
+    save_at(display, scale, colors, dir, prefix, SystemTime::now())
+}
+
+// save with a given timestamp instead of the current time
+pub fn save_at(
+    display: &Display,
+    scale: usize,
+    colors: &Colors,
+    dir: &Path,
+    prefix: &str,
+    time: SystemTime,
+) -> Result<PathBuf, png::EncodingError> {
+    fs::create_dir_all(dir)?;
+    let name = file_name(prefix, time);
+    let stem = name.trim_end_matches(".png");
+    let mut count = 0;
+    let (path, file) = loop {
+        let path = match count {
+            0 => dir.join(&name),
+            _ => dir.join(format!("{}-{}.png", stem, count)),
+        };
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Ok(file) => break (path, file),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => count += 1,
+            Err(e) => return Err(e.into()),
+        }
+    };
+    display.write_png(scale, colors, BufWriter::new(file))?;
+    Ok(path)
+}
+
+pub fn file_name(prefix: &str, time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (year, month, day) = civil_date((seconds / 86400) as i64);
+    let time_of_day = seconds % 86400;
+    format!(
+        "{}-{:04}{:02}{:02}-{:02}{:02}{:02}-{:03}.png",
+        prefix,
+        year,
+        month,
+        day,
+        time_of_day / 3600,
+        time_of_day / 60 % 60,
+        time_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+// Gregorian date of a day count since 1970-01-01, after Howard Hinnant's civil_from_days
+fn civil_date(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
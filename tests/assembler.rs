@@ -0,0 +1,291 @@
+// Octo style source to rom bytes
+use chip8::assembler::{assemble, AsmError};
+use chip8::{disasm, Chip8, Quirks};
+
+fn words(source: &str) -> Vec<u16> {
+    let rom = assemble(source).unwrap_or_else(|e| panic!("{}: {}", source, e));
+    rom.chunks(2)
+        .map(|pair| (pair[0] as u16) << 8 | *pair.get(1).unwrap_or(&0) as u16)
+        .collect()
+}
+
+fn error(source: &str) -> AsmError {
+    assemble(source).expect_err(source)
+}
+
+// Every statement and the opcode it assembles to
+const STATEMENTS: [(&str, u16); 50] = [
+    ("scroll-down 3", 0x00C3),
+    ("scroll-up 4", 0x00D4),
+    ("clear", 0x00E0),
+    ("return", 0x00EE),
+    (";", 0x00EE),
+    ("scroll-right", 0x00FB),
+    ("scroll-left", 0x00FC),
+    ("exit", 0x00FD),
+    ("lores", 0x00FE),
+    ("hires", 0x00FF),
+    ("jump 0xABC", 0x1ABC),
+    (":call 0x2BC", 0x22BC),
+    ("if va != 0x12 then", 0x3A12),
+    ("if vb == 0x34 then", 0x4B34),
+    ("if v1 != v2 then", 0x5120),
+    ("save v1 - v2", 0x5122),
+    ("load v1 - v2", 0x5123),
+    ("vc := 0x56", 0x6C56),
+    ("vd += 0x78", 0x7D78),
+    ("vd -= 1", 0x7DFF),
+    ("v3 := v4", 0x8340),
+    ("v3 |= v4", 0x8341),
+    ("v3 &= v4", 0x8342),
+    ("v3 ^= v4", 0x8343),
+    ("v3 += v4", 0x8344),
+    ("v3 -= v4", 0x8345),
+    ("v3 >>= v4", 0x8346),
+    ("v3 =- v4", 0x8347),
+    ("v3 <<= v4", 0x834E),
+    ("if v5 == v6 then", 0x9560),
+    ("i := 0x123", 0xA123),
+    ("jump0 0x234", 0xB234),
+    ("v7 := random 0xFF", 0xC7FF),
+    ("sprite v1 v2 5", 0xD125),
+    ("if v8 -key then", 0xE89E),
+    ("if v9 key then", 0xE9A1),
+    ("plane 2", 0xF201),
+    ("audio", 0xF002),
+    ("v1 := delay", 0xF107),
+    ("v2 := key", 0xF20A),
+    ("delay := v3", 0xF315),
+    ("buzzer := v4", 0xF418),
+    ("i += v5", 0xF51E),
+    ("i := hex v6", 0xF629),
+    ("i := bighex v7", 0xF730),
+    ("bcd v8", 0xF833),
+    ("pitch := v9", 0xF93A),
+    ("save va", 0xFA55),
+    ("load vb", 0xFB65),
+    ("saveflags vc", 0xFC75),
+];
+
+#[test]
+fn every_statement_assembles() {
+    for (source, opcode) in STATEMENTS.iter() {
+        assert_eq!(words(source), vec![*opcode], "{}", source);
+    }
+    assert_eq!(words("loadflags vd"), vec![0xFD85]);
+    assert_eq!(words("i := long 0x1234"), vec![0xF000, 0x1234]);
+    assert_eq!(words("VF := 0b1010"), vec![0x6F0A]);
+}
+
+#[test]
+fn labels_resolve_backwards_and_forwards() {
+    let source = "
+        : start
+          jump later      # forward
+        : back
+          :call start
+          i := sprite
+          i := long sprite
+          later           # a bare label calls it
+        : later
+          jump back
+        : sprite
+          0xF0 0x90
+    ";
+    assert_eq!(
+        assemble(source).unwrap(),
+        vec![
+            0x12, 0x0C, // jump later
+            0x22, 0x00, // :call start
+            0xA2, 0x0E, // i := sprite
+            0xF0, 0x00, 0x02, 0x0E, // i := long sprite
+            0x22, 0x0C, // later
+            0x12, 0x02, // jump back
+            0xF0, 0x90,
+        ]
+    );
+}
+
+#[test]
+fn main_gets_a_jump_when_it_isnt_first() {
+    assert_eq!(
+        words(": data 1 2\n: main\njump main"),
+        vec![0x1204, 0x0102, 0x1204]
+    );
+    assert_eq!(words(": main\nexit"), vec![0x00FD]);
+}
+
+#[test]
+fn data_bytes() {
+    let source = ":const SIZE 3\n:byte 0xFF :byte SIZE 7 -1 0b10000001";
+    assert_eq!(assemble(source).unwrap(), vec![0xFF, 3, 7, 0xFF, 0x81]);
+}
+
+#[test]
+fn consts_and_aliases() {
+    let source = ":const SPEED 4\n:alias x v3\nx := SPEED\nx += x\nsprite x x SPEED";
+    assert_eq!(words(source), vec![0x6304, 0x8334, 0xD334]);
+}
+
+#[test]
+fn blocks_jump_around_their_bodies() {
+    let source = "
+        if v0 == 1 begin
+          v1 := 1
+        else
+          v1 := 2
+        end
+        loop
+          v2 += 1
+          while v2 != 5
+        again
+    ";
+    assert_eq!(
+        words(source),
+        vec![
+            0x3001, 0x1208, // if v0 == 1 begin, skips the jump to else
+            0x6101, 0x120A, // then jumps past else
+            0x6102, // else
+            0x7201, // loop
+            0x4205, 0x1212, // while v2 != 5, leaves the loop
+            0x120A, // again
+        ]
+    );
+}
+
+#[test]
+fn errors_report_their_line() {
+    let cases = [
+        ("clear\njump nowhere", 2, "undefined label 'nowhere'"),
+        (": a\n: a", 2, "label 'a' is defined twice"),
+        ("v0 := 256", 1, "256 does not fit in a byte"),
+        (":byte 300", 1, "300 does not fit in a byte"),
+        ("sprite v0 v1 16", 1, "16 does not fit in 4 bits"),
+        ("plane 4", 1, "plane must be 0 to 3"),
+        (
+            "jump 0x1000",
+            1,
+            "address 0x1000 does not fit in 12 bits, use i := long",
+        ),
+        ("i := long 0x10000", 1, "address out of range"),
+        ("\n\nend", 3, "end without if ... begin"),
+        ("else", 1, "else without if ... begin"),
+        ("again", 1, "again without loop"),
+        ("while v0 == 1", 1, "while outside of a loop"),
+        ("loop\nclear", 1, "loop is missing its end"),
+        ("if v0 == 1 begin", 1, "if is missing its end"),
+        (
+            "if v0 < 1 then",
+            1,
+            "expected ==, !=, key or -key, found '<'",
+        ),
+        (
+            "if v0 == 1 clear",
+            1,
+            "expected then or begin, found 'clear'",
+        ),
+        ("v0 $ 1", 1, "expected an assignment, found '$'"),
+        ("v0 := vz", 1, "expected a number, found 'vz'"),
+        ("v0 :=", 1, "unexpected end of file"),
+        ("i -= v0", 1, "expected := or +=, found '-='"),
+        ("delay = v0", 1, "expected :=, found '='"),
+        (":const v0 1", 1, "expected a name, found 'v0'"),
+        ("@", 1, "expected a statement, found '@'"),
+    ];
+    for (source, line, message) in cases.iter() {
+        let e = error(source);
+        assert_eq!(
+            (e.line, e.message.as_str()),
+            (*line, *message),
+            "{}",
+            source
+        );
+    }
+    assert_eq!(
+        error("\nv0 := 256").to_string(),
+        "line 2: 256 does not fit in a byte"
+    );
+}
+
+#[test]
+fn comments_are_ignored() {
+    assert_eq!(words("clear # v0 := 256\n# exit"), vec![0x00E0]);
+}
+
+#[test]
+fn disassembled_roms_assemble_to_the_same_bytes() {
+    let roms = [
+        "games/BLINKY",
+        "games/BLITZ",
+        "games/BRIX",
+        "games/INVADERS",
+        "games/MAZE",
+        "games/PONG",
+        "games/PONG2",
+        "games/TANK",
+        "games/TETRIS",
+        "tests/BC_test.ch8",
+        "tests/c8_test.c8",
+        "tests/test_opcode.ch8",
+    ];
+    for path in roms.iter() {
+        let rom = std::fs::read(path).unwrap();
+        for xo_chip in [false, true].iter() {
+            let source = disasm::to_octo(&rom, *xo_chip);
+            let assembled = assemble(&source).unwrap_or_else(|e| panic!("{}: {}", path, e));
+            assert_eq!(assembled, rom, "{}", path);
+        }
+    }
+}
+
+#[test]
+fn source_loads_straight_into_the_machine() {
+    let mut chip8 = Chip8::new(Quirks::default());
+    chip8
+        .load_source(
+            ": main
+               v0 := 0
+               loop
+                 v0 += 1
+                 if v0 != 3 then
+               again
+               i := digit
+               sprite v1 v2 1
+             : halt
+               jump halt
+             : digit
+               0x80",
+        )
+        .unwrap();
+    for _ in 0..20 {
+        chip8.step().unwrap();
+    }
+    assert_eq!(chip8.cpu.reg[0], 3);
+    assert_eq!(chip8.bus.display.framebuffer[0][0], 1);
+
+    let mut chip8 = Chip8::new(Quirks::default());
+    let error = chip8
+        .load_source("0 ".repeat(0x1000).trim_end())
+        .unwrap_err();
+    assert_eq!(error.line, 1);
+    assert!(error.message.contains("does not fit"), "{}", error);
+    let error = chip8.load_source("v0 := 1\nfoo").unwrap_err();
+    assert_eq!(error.line, 2);
+}
+
+#[test]
+fn every_opcode_survives_the_round_trip() {
+    // Straight line code through every statement, ending in exit so all of it is reachable
+    let mut rom: Vec<u8> = STATEMENTS
+        .iter()
+        .filter(|(source, _)| !source.starts_with("jump") && !source.starts_with(';'))
+        .filter(|(source, _)| *source != "return" && *source != "exit")
+        .flat_map(|(_, opcode)| opcode.to_be_bytes().to_vec())
+        .collect();
+    rom.extend_from_slice(&[0xF0, 0x00, 0x12, 0x34, 0xFD, 0x85, 0x00, 0xFD, 0xF0, 0x0F]);
+    let source = disasm::to_octo(&rom, true);
+    // Only the two bytes after exit are left as data
+    let data = source.lines().filter(|l| l.starts_with("  0x")).count();
+    assert_eq!(data, 2, "{}", source);
+    assert_eq!(assemble(&source).unwrap(), rom);
+}
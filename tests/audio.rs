@@ -0,0 +1,123 @@
+// The sound timer beep rendered headless into a buffer
+use chip8::audio::{self, AudioSink, BufferSink, NullSink, WavSink, Waveform};
+use chip8::{Chip8, Quirks};
+
+// Sets the sound timer to 10 then loops
+const BEEP: [u8; 6] = [0x6A, 0x0A, 0xFA, 0x18, 0x12, 0x04];
+
+fn beep(sink: &mut dyn AudioSink, frames: u64) -> Chip8 {
+    let mut chip8 = Chip8::new(Quirks::default());
+    chip8.load_rom(&BEEP).unwrap();
+    chip8.audio.sample_rate = sink.sample_rate();
+    for _ in 0..frames {
+        chip8.run_frame().unwrap();
+        chip8.audio.flush(sink);
+    }
+    chip8
+}
+
+#[test]
+fn beep_follows_the_sound_timer() {
+    let mut sink = BufferSink::new(48000);
+    beep(&mut sink, 20);
+    assert_eq!(sink.samples.len(), 20 * 800);
+    let (on, off) = sink.samples.split_at(10 * 800);
+    assert!(on.iter().all(|s| s.abs() == 0.25));
+    assert!(off.iter().all(|s| *s == 0.0));
+}
+
+#[test]
+fn sample_count_does_not_drift() {
+    let mut sink = BufferSink::new(44100);
+    beep(&mut sink, 600);
+    assert_eq!(sink.samples.len(), 10 * 44100);
+}
+
+#[test]
+fn square_wave_has_the_configured_pitch() {
+    let mut chip8 = Chip8::new(Quirks::default());
+    chip8.load_rom(&BEEP).unwrap();
+    chip8.audio.sample_rate = 48000;
+    chip8.audio.beeper.frequency = 750.0;
+    chip8.audio.beeper.volume = 0.5;
+    chip8.run_frame().unwrap();
+    let mut sink = BufferSink::new(48000);
+    chip8.audio.flush(&mut sink);
+
+    // 64 samples per period, high for the first half
+    let period = &sink.samples[..64];
+    assert!(period[..32].iter().all(|s| *s == 0.5));
+    assert!(period[32..].iter().all(|s| *s == -0.5));
+    assert_eq!(&sink.samples[64..128], period);
+}
+
+#[test]
+fn waveforms_stay_in_range() {
+    for name in ["square", "triangle", "sawtooth", "sine"].iter() {
+        let mut chip8 = Chip8::new(Quirks::default());
+        chip8.load_rom(&BEEP).unwrap();
+        chip8.audio.sample_rate = 48000;
+        chip8.audio.beeper.waveform = Waveform::parse(name).unwrap();
+        chip8.audio.beeper.volume = 1.0;
+        chip8.run_frame().unwrap();
+        let samples = &chip8.audio.samples;
+        assert!(samples.iter().all(|s| (-1.0..=1.0).contains(s)), "{}", name);
+        assert!(samples.iter().any(|s| *s > 0.9), "{}", name);
+        assert!(samples.iter().any(|s| *s < -0.9), "{}", name);
+    }
+}
+
+#[test]
+fn null_sink_generates_nothing() {
+    let chip8 = beep(&mut NullSink, 20);
+    assert!(chip8.audio.samples.is_empty());
+}
+
+#[test]
+fn wav_recording_matches_the_timer() {
+    // Per process so concurrent test runs don't share the file
+    let dir = std::env::temp_dir().join(format!("chip8-wav-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("beep.wav");
+    let mut sink = WavSink::create(&path, 48000).unwrap();
+    beep(&mut sink, 20);
+    sink.finish().unwrap();
+
+    let mut reader = hound::WavReader::open(&path).unwrap();
+    assert_eq!(reader.spec().sample_rate, 48000);
+    assert_eq!(reader.spec().channels, 1);
+    let samples: Vec<i16> = reader.samples().map(|s| s.unwrap()).collect();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(samples.len(), 20 * 800);
+    assert!(samples[..10 * 800].iter().all(|s| s.abs() == i16::MAX / 4));
+    assert!(samples[10 * 800..].iter().all(|s| *s == 0));
+}
+
+#[test]
+fn xo_chip_pattern_plays_at_its_pitch() {
+    assert_eq!(audio::pattern_rate(64), 4000.0);
+    assert_eq!(audio::pattern_rate(112), 8000.0);
+
+    // 64 bits on then 64 bits off, at the default pitch of 4000 bits per second
+    let mut rom = vec![0xA2, 0x0A, 0xF0, 0x02, 0x6A, 0x02, 0xFA, 0x18, 0x12, 0x08];
+    rom.extend_from_slice(&[0xFF; 8]);
+    rom.extend_from_slice(&[0x00; 8]);
+    let mut chip8 = Chip8::new(Quirks::xo_chip());
+    chip8.load_rom(&rom).unwrap();
+    chip8.audio.sample_rate = 8000;
+    chip8.audio.beeper.volume = 1.0;
+    for _ in 0..4 {
+        chip8.run_frame().unwrap();
+    }
+
+    // Loading the pattern takes longer than the time left before the first tick
+    let (first, samples) = chip8.audio.samples.split_at(133);
+    assert!(first.iter().all(|s| *s == 0.0));
+    // Two samples per bit
+    assert_eq!(samples.len(), 400);
+    assert!(samples[..128].iter().all(|s| *s == 1.0));
+    assert!(samples[128..256].iter().all(|s| *s == -1.0));
+    // The sound timer runs for frames of 133 and 134 samples
+    assert!(samples[256..267].iter().all(|s| *s == 1.0));
+    assert!(samples[267..].iter().all(|s| *s == 0.0));
+}
@@ -0,0 +1,13 @@
+use chip8::Quirks;
+
+// Every quirk preset the emulator ships with
+pub fn presets() -> Vec<(&'static str, Quirks)> {
+    vec![
+        ("default", Quirks::default()),
+        ("vip", Quirks::cosmac_vip()),
+        ("chip48", Quirks::chip48()),
+        ("schip", Quirks::superchip()),
+        ("modern", Quirks::modern()),
+        ("xo-chip", Quirks::xo_chip()),
+    ]
+}
@@ -0,0 +1,157 @@
+// Keymaps read from the frontend config
+#![cfg(feature = "frontend")]
+
+// The config is part of the binary, not the library
+#[path = "../src/frontend/config.rs"]
+mod config;
+// Only the bindings are used here, not the gamepads and the keyboard input
+#[allow(dead_code)]
+#[path = "../src/frontend/gamepad.rs"]
+mod gamepad;
+#[allow(dead_code)]
+#[path = "../src/frontend/keymap.rs"]
+mod keymap;
+
+use config::{Config, ConfigError};
+use gamepad::{PadInput, Padmap};
+use keymap::Keymap;
+use winit::event::VirtualKeyCode;
+
+const CONFIG: &str = r#"
+[keys]
+5 = ["W", "Up"]
+a = ["Space"]
+
+[gamepad]
+0 = ["East"]
+
+[roms.PONG.keys]
+1 = ["Key1", "Up"]
+5 = ["Return"]
+
+[roms.PONG.gamepad]
+1 = ["DPadUp", "LeftStickUp"]
+2 = []
+"#;
+
+#[test]
+fn empty_config_keeps_the_default_layout() {
+    let config = Config::parse("").unwrap();
+    assert_eq!(config.keymap("PONG"), Keymap::new());
+    assert_eq!(Keymap::new().keys[0xC], vec![VirtualKeyCode::Z]);
+}
+
+#[test]
+fn keys_bind_several_host_keys() {
+    let keymap = Config::parse(CONFIG).unwrap().keymap("TANK");
+    assert_eq!(keymap.keys[5], vec![VirtualKeyCode::W, VirtualKeyCode::Up]);
+    assert_eq!(keymap.keys[0xA], vec![VirtualKeyCode::Space]);
+    assert_eq!(keymap.keys[0], vec![VirtualKeyCode::Key1]);
+}
+
+#[test]
+fn rom_overrides_apply_on_top() {
+    let keymap = Config::parse(CONFIG).unwrap().keymap("PONG");
+    assert_eq!(
+        keymap.keys[1],
+        vec![VirtualKeyCode::Key1, VirtualKeyCode::Up]
+    );
+    assert_eq!(keymap.keys[5], vec![VirtualKeyCode::Return]);
+    assert_eq!(keymap.keys[0xA], vec![VirtualKeyCode::Space]);
+}
+
+#[test]
+fn gamepad_bindings_follow_the_same_rules() {
+    let config = Config::parse(CONFIG).unwrap();
+    let padmap = config.padmap("TANK");
+    assert_eq!(padmap.keys[0], vec![PadInput::East]);
+    assert_eq!(padmap.keys[5], Padmap::new().keys[5]);
+    assert_eq!(
+        padmap.keys[2],
+        vec![PadInput::DPadUp, PadInput::LeftStickUp]
+    );
+
+    let padmap = config.padmap("PONG");
+    assert_eq!(
+        padmap.keys[1],
+        vec![PadInput::DPadUp, PadInput::LeftStickUp]
+    );
+    assert!(padmap.keys[2].is_empty());
+    assert_eq!(padmap.keys[0], vec![PadInput::East]);
+}
+
+#[test]
+fn bad_keys_are_rejected() {
+    let error = Config::parse("[keys]\nG = [\"Q\"]").unwrap_err();
+    assert!(matches!(&error, ConfigError::BadKey(key) if key == "G"));
+    let error = Config::parse("[roms.PONG.keys]\n10 = [\"Q\"]").unwrap_err();
+    assert!(matches!(error, ConfigError::BadKey(_)));
+    let error = Config::parse("[keys]\n1 = [\"NotAKey\"]").unwrap_err();
+    assert!(matches!(error, ConfigError::Parse(_)));
+    let error = Config::parse("[gamepad]\nX = [\"South\"]").unwrap_err();
+    assert!(matches!(error, ConfigError::BadKey(_)));
+    let error = Config::parse("[gamepad]\n1 = [\"Q\"]").unwrap_err();
+    assert!(matches!(error, ConfigError::Parse(_)));
+    let error = Config::parse("[kes]\n1 = [\"Q\"]").unwrap_err();
+    assert!(matches!(error, ConfigError::Parse(_)));
+    let error = Config::parse("[keys]\n\"+\" = [\"Q\"]").unwrap_err();
+    assert!(matches!(error, ConfigError::BadKey(_)));
+    let error = Config::parse("[keys]\n\"é\" = [\"Q\"]").unwrap_err();
+    assert!(matches!(error, ConfigError::BadKey(_)));
+    let error = Config::parse("[keys]\n1 = \"Q\"").unwrap_err();
+    assert!(matches!(error, ConfigError::Parse(_)));
+}
+
+#[test]
+fn palettes_come_from_the_config_and_the_rom() {
+    let config = Config::parse(
+        r##"
+palette = "amber"
+
+[palettes]
+paper = ["#FFFFFF", "#000000"]
+green = ["#000000", "#00FF00"]
+
+[roms.PONG]
+palette = "paper"
+"##,
+    )
+    .unwrap();
+    assert_eq!(config.palette("PONG"), Some("paper"));
+    assert_eq!(config.palette("TANK"), Some("amber"));
+    let palettes = config.palettes().unwrap();
+    let paper = palettes.iter().find(|p| p.name == "paper").unwrap();
+    assert_eq!(paper.colors[1], [0, 0, 0, 0xFF]);
+    // A custom palette replaces the built-in one of the same name
+    let green = palettes.iter().filter(|p| p.name == "green").count();
+    assert_eq!(green, 1);
+    assert_eq!(palettes.len(), 7);
+}
+
+#[test]
+fn unknown_palettes_are_rejected() {
+    let result = Config::parse("palette = \"sepia\"");
+    assert!(matches!(result, Err(ConfigError::BadPalette(_))));
+    let result = Config::parse("[palettes]\nsepia = [\"#000000\"]");
+    assert!(matches!(result, Err(ConfigError::BadPalette(_))));
+}
+
+#[test]
+fn seed_can_be_set_per_rom() {
+    let config = Config::parse("seed = 1234\n[roms.PONG]\nseed = 7").unwrap();
+    assert_eq!(config.seed("PONG"), Some(7));
+    assert_eq!(config.seed("INVADERS"), Some(1234));
+    assert_eq!(Config::parse("").unwrap().seed("PONG"), None);
+    let result = Config::parse("seed = -1");
+    assert!(matches!(result, Err(ConfigError::Parse(_))));
+}
+
+#[test]
+fn phosphor_decay_can_be_set_per_rom() {
+    let config = Config::parse("phosphor = 60\n[roms.PONG]\nphosphor = 0").unwrap();
+    assert_eq!(config.phosphor("PONG"), Some(0));
+    assert_eq!(config.phosphor("INVADERS"), Some(60));
+    assert_eq!(Config::parse("").unwrap().phosphor("PONG"), None);
+    let result = Config::parse("phosphor = 101");
+    assert!(matches!(result, Err(ConfigError::BadPhosphor(101))));
+}
@@ -0,0 +1,193 @@
+// Breakpoints, watchpoints and the debugger commands
+use chip8::debugger::{Debugger, Stop};
+use chip8::ram::Access;
+use chip8::{Chip8, Quirks};
+
+// Stores V0 at 0x300, reads it back and calls a subroutine before looping forever
+const PROGRAM: [u16; 10] = [
+    0x6005, // 0x200 v0 := 5
+    0x7001, // 0x202 v0 += 1
+    0xA300, // 0x204 i := 0x300
+    0xF055, // 0x206 save v0
+    0xF065, // 0x208 load v0
+    0x2210, // 0x20A call 0x210
+    0x120C, // 0x20C jump 0x20C
+    0x0000, //
+    0x6107, // 0x210 v1 := 7
+    0x00EE, // 0x212 return
+];
+
+fn machine() -> Chip8 {
+    let mut chip8 = Chip8::new(Quirks::default());
+    let rom: Vec<u8> = PROGRAM.iter().flat_map(|op| op.to_be_bytes()).collect();
+    chip8.load_rom(&rom).unwrap();
+    chip8
+}
+
+// Runs until the debugger stops or the program has long reached its loop
+fn run(debugger: &mut Debugger, chip8: &mut Chip8) -> Option<Stop> {
+    for _ in 0..1000 {
+        if let Some(stop) = debugger.exec_cycle(chip8).unwrap() {
+            return Some(stop);
+        }
+    }
+    None
+}
+
+#[test]
+fn breakpoints_stop_before_the_instruction() {
+    let mut chip8 = machine();
+    let mut debugger = Debugger::new();
+    assert_eq!(
+        debugger.command(&mut chip8, "b 206"),
+        "breakpoint at 0x0206"
+    );
+    assert_eq!(
+        run(&mut debugger, &mut chip8),
+        Some(Stop::Breakpoint(0x206))
+    );
+    assert!(debugger.paused);
+    assert_eq!(chip8.cpu.pc, 0x206);
+    assert_eq!(chip8.cpu.reg[0], 6);
+
+    // Continuing steps past the breakpoint it stopped on
+    debugger.command(&mut chip8, "c");
+    assert_eq!(run(&mut debugger, &mut chip8), None);
+    assert_eq!(chip8.cpu.pc, 0x20C);
+
+    assert_eq!(
+        debugger.command(&mut chip8, "d 206"),
+        "deleted breakpoint at 0x0206"
+    );
+    assert!(debugger.command(&mut chip8, "d 206").starts_with("error"));
+}
+
+#[test]
+fn conditional_breakpoints_check_registers() {
+    let mut chip8 = machine();
+    let mut debugger = Debugger::new();
+    debugger.command(&mut chip8, "b 204 if v0 != 6");
+    debugger.command(&mut chip8, "b 208 if v0 == 6");
+    assert_eq!(
+        run(&mut debugger, &mut chip8),
+        Some(Stop::Breakpoint(0x208))
+    );
+    assert!(debugger
+        .command(&mut chip8, "b 200 if v0 == 10000")
+        .starts_with("error: invalid condition"));
+}
+
+#[test]
+fn write_watchpoints_stop_after_the_store() {
+    let mut chip8 = machine();
+    let mut debugger = Debugger::new();
+    assert_eq!(
+        debugger.command(&mut chip8, "w w 300"),
+        "watching 0x0300..0x0301"
+    );
+    let access = Access {
+        address: 0x300,
+        len: 1,
+        write: true,
+    };
+    assert_eq!(
+        run(&mut debugger, &mut chip8),
+        Some(Stop::Watchpoint { pc: 0x206, access })
+    );
+    assert_eq!(chip8.bus.ram.mem[0x300], 6);
+}
+
+#[test]
+fn read_watchpoints_ignore_writes() {
+    let mut chip8 = machine();
+    let mut debugger = Debugger::new();
+    debugger.command(&mut chip8, "w r 2FF 2");
+    match run(&mut debugger, &mut chip8) {
+        Some(Stop::Watchpoint { pc, access }) => {
+            assert_eq!(pc, 0x208);
+            assert!(!access.write);
+        }
+        stop => panic!("expected a watchpoint, got {:?}", stop),
+    }
+    assert!(debugger
+        .command(&mut chip8, "l")
+        .contains("watch Read 0x02FF..0x0301"));
+    debugger.command(&mut chip8, "dw 2FF");
+    assert!(debugger.watchpoints.is_empty());
+}
+
+#[test]
+fn watchpoints_outside_memory_are_rejected() {
+    let mut chip8 = machine();
+    let mut debugger = Debugger::new();
+    for command in [
+        "w rw FFFFFFFFFFFFFFFF 2",
+        "w rw 1000",
+        "w rw FFF 2",
+        "w rw 200 FFFFFFFFFFFFFFFF",
+        "w r 200 0",
+        "b 1000",
+    ]
+    .iter()
+    {
+        let output = debugger.command(&mut chip8, command);
+        assert!(output.starts_with("error"), "{}: {}", command, output);
+    }
+    assert!(debugger.watchpoints.is_empty());
+    assert!(debugger.breakpoints.is_empty());
+}
+
+#[test]
+fn stepping_over_and_out_of_calls() {
+    let mut chip8 = machine();
+    let mut debugger = Debugger::new();
+    debugger.command(&mut chip8, "s 5");
+    assert_eq!(chip8.cpu.pc, 0x20A);
+    debugger.command(&mut chip8, "n");
+    assert_eq!(run(&mut debugger, &mut chip8), Some(Stop::Step(0x20C)));
+    assert_eq!(chip8.cpu.reg[1], 7);
+
+    let mut chip8 = machine();
+    debugger.command(&mut chip8, "s 7");
+    assert_eq!(chip8.cpu.pc, 0x212);
+    debugger.command(&mut chip8, "f");
+    assert_eq!(run(&mut debugger, &mut chip8), Some(Stop::Step(0x20C)));
+    assert!(debugger.command(&mut chip8, "f").starts_with("error"));
+}
+
+#[test]
+fn mem_dumps_memory() {
+    let mut chip8 = machine();
+    let mut debugger = Debugger::new();
+    assert_eq!(
+        debugger.command(&mut chip8, "mem 200 4"),
+        "0x0200: 60 05 70 01"
+    );
+    let rows = debugger.command(&mut chip8, "m 0x200 14");
+    assert_eq!(
+        rows,
+        "0x0200: 60 05 70 01 A3 00 F0 55 F0 65 22 10 12 0C 00 00\n0x0210: 61 07 00 EE"
+    );
+    // Lengths past the end stop at the end of memory
+    let tail = debugger.command(&mut chip8, "m FFE FFFFFFFFFFFFFFFF");
+    assert_eq!(tail, "0x0FFE: 00 00");
+    for command in ["mem FFFFFFFFFFFFFFFF 1", "mem 1000", "mem xyz"].iter() {
+        let output = debugger.command(&mut chip8, command);
+        assert!(output.starts_with("error"), "{}: {}", command, output);
+    }
+}
+
+#[test]
+fn reg_dumps_the_registers() {
+    let mut chip8 = machine();
+    let mut debugger = Debugger::new();
+    debugger.command(&mut chip8, "s 3");
+    assert_eq!(
+        debugger.command(&mut chip8, "r"),
+        "PC 0x0206  I 0x0300  DT 00  ST 00  SP 0\n\
+         V0 06  V1 00  V2 00  V3 00  V4 00  V5 00  V6 00  V7 00\n\
+         V8 00  V9 00  VA 00  VB 00  VC 00  VD 00  VE 00  VF 00"
+    );
+    assert_eq!(debugger.command(&mut chip8, "stack"), "stack is empty");
+    assert!(debugger.command(&mut chip8, "bogus").starts_with("error"));
+}
@@ -0,0 +1,162 @@
+// Instruction decoding, mnemonics and the disassembler listing
+use chip8::disasm;
+use chip8::instruction::Instruction;
+
+// Opcode, the word after it, the XO-CHIP flag and the expected mnemonic
+const TABLE: [(u16, Option<u16>, bool, &str); 55] = [
+    (0x00C3, None, false, "SCD 3"),
+    (0x00D4, None, true, "SCU 4"),
+    (0x00E0, None, false, "CLS"),
+    (0x00EE, None, false, "RET"),
+    (0x00FB, None, false, "SCR"),
+    (0x00FC, None, false, "SCL"),
+    (0x00FD, None, false, "EXIT"),
+    (0x00FE, None, false, "LOW"),
+    (0x00FF, None, false, "HIGH"),
+    (0x1ABC, None, false, "JP 0xABC"),
+    (0x2ABC, None, false, "CALL 0xABC"),
+    (0x3A12, None, false, "SE VA, 0x12"),
+    (0x4B34, None, false, "SNE VB, 0x34"),
+    (0x5120, None, false, "SE V1, V2"),
+    (0x5122, None, true, "SAVE V1-V2"),
+    (0x5123, None, true, "LOAD V1-V2"),
+    (0x6C56, None, false, "LD VC, 0x56"),
+    (0x7D78, None, false, "ADD VD, 0x78"),
+    (0x8340, None, false, "LD V3, V4"),
+    (0x8341, None, false, "OR V3, V4"),
+    (0x8342, None, false, "AND V3, V4"),
+    (0x8343, None, false, "XOR V3, V4"),
+    (0x8344, None, false, "ADD V3, V4"),
+    (0x8345, None, false, "SUB V3, V4"),
+    (0x8346, None, false, "SHR V3, V4"),
+    (0x8347, None, false, "SUBN V3, V4"),
+    (0x834E, None, false, "SHL V3, V4"),
+    (0x9560, None, false, "SNE V5, V6"),
+    (0xA123, None, false, "LD I, 0x123"),
+    (0xB034, None, false, "JP V0, 0x034"),
+    (0xB234, None, false, "JP V0, 0x234 (V2 with jump_vx)"),
+    (0xC7FF, None, false, "RND V7, 0xFF"),
+    (0xD125, None, false, "DRW V1, V2, 5"),
+    (0xD120, None, false, "DRW V1, V2, 0"),
+    (0xE89E, None, false, "SKP V8"),
+    (0xE9A1, None, false, "SKNP V9"),
+    (0xF000, Some(0x1234), true, "LD I, LONG 0x1234"),
+    (0xF201, None, true, "PLANE 2"),
+    (0xF002, None, true, "AUDIO"),
+    (0xF107, None, false, "LD V1, DT"),
+    (0xF20A, None, false, "LD V2, K"),
+    (0xF315, None, false, "LD DT, V3"),
+    (0xF418, None, false, "LD ST, V4"),
+    (0xF51E, None, false, "ADD I, V5"),
+    (0xF629, None, false, "LD F, V6"),
+    (0xF730, None, false, "LD HF, V7"),
+    (0xF833, None, false, "LD B, V8"),
+    (0xF93A, None, true, "PITCH V9"),
+    (0xFA55, None, false, "LD [I], VA"),
+    (0xFB65, None, false, "LD VB, [I]"),
+    (0xFC75, None, false, "LD R, VC"),
+    (0xFD85, None, false, "LD VD, R"),
+    // Decoded the same with XO-CHIP enabled
+    (0x00E0, None, true, "CLS"),
+    (0xD125, None, true, "DRW V1, V2, 5"),
+    (0xF20A, None, true, "LD V2, K"),
+];
+
+// Opcodes without a meaning, and XO-CHIP ones outside XO-CHIP mode
+const INVALID: [(u16, Option<u16>, bool); 19] = [
+    (0x0000, None, false),
+    (0x00E1, None, false),
+    (0x0123, None, false),
+    (0x00D4, None, false),
+    (0x5121, None, true),
+    (0x5122, None, false),
+    (0x5123, None, false),
+    (0x9561, None, false),
+    (0x956F, None, true),
+    (0x8348, None, false),
+    (0x834F, None, false),
+    (0xE19F, None, false),
+    (0xF1FF, None, true),
+    (0xF000, Some(0x1234), false),
+    // F000 needs the word after it
+    (0xF000, None, true),
+    (0xF100, Some(0x1234), true),
+    (0xF201, None, false),
+    (0xF102, None, true),
+    (0xF93A, None, false),
+];
+
+#[test]
+fn every_opcode_decodes_to_its_mnemonic() {
+    for (opcode, next, xo_chip, mnemonic) in TABLE.iter() {
+        let instruction = Instruction::decode(*opcode, *next, *xo_chip)
+            .unwrap_or_else(|| panic!("{:04X} didn't decode", opcode));
+        assert_eq!(instruction.to_string(), *mnemonic, "{:04X}", opcode);
+        let size = if *opcode == 0xF000 { 4 } else { 2 };
+        assert_eq!(instruction.size(), size, "{:04X}", opcode);
+    }
+}
+
+#[test]
+fn invalid_opcodes_dont_decode() {
+    for (opcode, next, xo_chip) in INVALID.iter() {
+        assert_eq!(
+            Instruction::decode(*opcode, *next, *xo_chip),
+            None,
+            "{:04X}",
+            opcode
+        );
+    }
+}
+
+#[test]
+fn decoding_splits_the_operands() {
+    assert_eq!(
+        Instruction::decode(0xBA23, None, false),
+        Some(Instruction::JpV0(0xA, 0xA23))
+    );
+    assert_eq!(
+        Instruction::decode(0xDEF7, None, false),
+        Some(Instruction::Drw(0xE, 0xF, 7))
+    );
+    assert_eq!(
+        Instruction::decode(0xF000, Some(0xFFFF), true),
+        Some(Instruction::LdILong(0xFFFF))
+    );
+}
+
+#[test]
+fn listing_follows_code_and_shows_data() {
+    // Jumps over a sprite byte, calls a subroutine and skips over a long load
+    let rom = [
+        0x12, 0x04, // 0x200 jp 0x204
+        0xF0, // 0x202 sprite
+        0x00, //
+        0x22, 0x0E, // 0x204 call 0x20E
+        0x30, 0x00, // 0x206 se v0, 0
+        0xF0, 0x00, 0x02, 0x02, // 0x208 ld i, long 0x202
+        0x00, 0xFD, // 0x20C exit
+        0x00, 0xEE, // 0x20E ret
+    ];
+    let listing = disasm::disassemble(&rom, true);
+    let expected = "\
+0x0200  1204      JP 0x204
+0x0202  F0        db 0xF0  ; ####....
+0x0203  00        db 0x00  ; ........
+L204:
+0x0204  220E      CALL 0x20E
+0x0206  3000      SE V0, 0x00
+0x0208  F0000202  LD I, LONG 0x0202
+0x020C  00FD      EXIT
+L20E:
+0x020E  00EE      RET
+";
+    assert_eq!(listing, expected);
+
+    // Without XO-CHIP F000 isn't code, the skip leads into its second word and the
+    // exit after it is never reached
+    let listing = disasm::disassemble(&rom, false);
+    assert!(listing.contains("0x0208  F0        db 0xF0  ; ####....\n"));
+    assert!(listing.contains("0x020A  02        db 0x02  ; ......#.\n"));
+    assert!(listing.contains("0x020D  FD        db 0xFD  ; ######.#\n"));
+}
@@ -0,0 +1,79 @@
+// The text format of key input movies
+use chip8::movie::{KeyChange, Movie, MovieError};
+use chip8::Quirks;
+
+const ROM: &[u8] = &[0x60, 0x05, 0x12, 0x02];
+
+fn error(text: &str) -> MovieError {
+    Movie::parse(text).expect_err(text)
+}
+
+fn movie_text(quirks: &str) -> String {
+    let hash = chip8::movie::rom_hash(ROM);
+    format!(
+        "chip8-movie 1\nrom {:#018x}\nseed 1234\nquirks {}\nlength 300\n120 0x0020\n",
+        hash, quirks
+    )
+}
+
+#[test]
+fn movies_survive_the_round_trip() {
+    let mut movie = Movie::new(ROM, 1234, Quirks::xo_chip());
+    movie.record(0, 0);
+    movie.record(120, 0x0020);
+    movie.record(121, 0x0020);
+    movie.record(135, 0x8001);
+    movie.record(300, 0);
+    assert_eq!(movie.changes.len(), 3);
+
+    let parsed = Movie::parse(&movie.to_string()).unwrap();
+    assert_eq!(parsed, movie);
+    assert_eq!(parsed.keys_at(130), 0x0020);
+    assert_eq!(parsed.length, 300);
+    parsed.check_rom(ROM).unwrap();
+}
+
+#[test]
+fn hand_written_movies_parse() {
+    let movie = Movie::parse(&movie_text("0x7f")).unwrap();
+    assert_eq!(movie.seed, 1234);
+    assert_eq!(movie.quirks.bits(), 0x7F);
+    assert_eq!(
+        movie.changes,
+        vec![KeyChange {
+            frame: 120,
+            keys: 0x20
+        }]
+    );
+}
+
+#[test]
+fn bad_headers_are_rejected() {
+    assert_eq!(error("").line, 1);
+    assert_eq!(error("chip8-movie 2\n").line, 1);
+    let text = movie_text("0x00").replace("seed", "sed");
+    assert_eq!(error(&text).line, 3);
+    let text = movie_text("0x00").replace("length 300\n", "");
+    assert_eq!(error(&text).line, 5);
+    let text = movie_text("0x00") + "130 0x10000\n";
+    assert_eq!(error(&text).line, 7);
+    let text = movie_text("0x00") + "100 0x0001\n";
+    assert_eq!(error(&text).message, "frames must be increasing");
+}
+
+#[test]
+fn other_roms_are_rejected() {
+    let movie = Movie::parse(&movie_text("0x00")).unwrap();
+    let error = movie.check_rom(&[0x60, 0x06, 0x12, 0x02]).unwrap_err();
+    assert_eq!(error.line, 2);
+    assert_eq!(error.to_string(), "line 2: recorded with a different rom");
+}
+
+#[test]
+fn out_of_range_quirks_are_rejected() {
+    assert_eq!(error(&movie_text("0x80")).line, 4);
+    // Used to be cut to the low 8 bits
+    let wrapped = error(&movie_text("0x100"));
+    assert_eq!(wrapped.line, 4);
+    assert!(wrapped.message.contains("0x100"), "{}", wrapped);
+}
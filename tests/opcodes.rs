@@ -0,0 +1,665 @@
+// One test per instruction group, each run under every quirk preset
+mod common;
+
+use chip8::{Chip8, Chip8Error, Quirks};
+use common::presets;
+
+// Loads the opcodes at 0x200
+fn machine(quirks: Quirks, program: &[u16]) -> Chip8 {
+    let mut chip8 = Chip8::new(quirks);
+    chip8.seed(0);
+    let rom: Vec<u8> = program.iter().flat_map(|op| op.to_be_bytes()).collect();
+    chip8.load_rom(&rom).unwrap();
+    chip8
+}
+
+// Executes n instructions, letting the clock run while the cpu waits for the display
+fn exec(chip8: &mut Chip8, n: u64) -> Result<(), Chip8Error> {
+    let target = chip8.instructions + n;
+    let frame = chip8.frame;
+    while chip8.instructions < target {
+        assert!(
+            chip8.frame < frame + 10,
+            "cpu is stuck at {:#06X}",
+            chip8.cpu.pc
+        );
+        chip8.exec_cycle()?;
+    }
+    Ok(())
+}
+
+fn lit_pixels(chip8: &Chip8) -> usize {
+    let display = &chip8.bus.display;
+    display
+        .framebuffer
+        .iter()
+        .take(display.height())
+        .map(|row| {
+            row.iter()
+                .take(display.width())
+                .filter(|p| **p != 0)
+                .count()
+        })
+        .sum()
+}
+
+#[test]
+fn cls_00e0() {
+    for (name, quirks) in presets() {
+        // Draws the 0 glyph then clears the screen
+        let mut chip8 = machine(quirks, &[0xA000, 0xD005, 0x00E0]);
+        exec(&mut chip8, 2).unwrap();
+        assert_eq!(lit_pixels(&chip8), 14, "{}", name);
+        exec(&mut chip8, 1).unwrap();
+        assert_eq!(lit_pixels(&chip8), 0, "{}", name);
+    }
+}
+
+#[test]
+fn call_2nnn_and_ret_00ee() {
+    for (name, quirks) in presets() {
+        let mut chip8 = machine(quirks, &[0x2206, 0x6001, 0x0000, 0x00EE]);
+        exec(&mut chip8, 1).unwrap();
+        assert_eq!(chip8.cpu.pc, 0x206, "{}", name);
+        assert_eq!(chip8.cpu.stack(), &[0x202], "{}", name);
+        exec(&mut chip8, 2).unwrap();
+        assert_eq!(chip8.cpu.pc, 0x204, "{}", name);
+        assert!(chip8.cpu.stack().is_empty(), "{}", name);
+        assert_eq!(chip8.cpu.reg[0], 1, "{}", name);
+    }
+}
+
+#[test]
+fn ret_00ee_with_empty_stack_fails() {
+    for (name, quirks) in presets() {
+        let mut chip8 = machine(quirks, &[0x00EE]);
+        let error = Chip8Error::StackUnderflow {
+            pc: 0x200,
+            instruction: 0x00EE,
+        };
+        assert_eq!(exec(&mut chip8, 1), Err(error), "{}", name);
+    }
+}
+
+#[test]
+fn call_2nnn_overflows_after_16_levels() {
+    for (name, quirks) in presets() {
+        let mut chip8 = machine(quirks, &[0x2200]);
+        exec(&mut chip8, 16).unwrap();
+        let error = Chip8Error::StackOverflow {
+            pc: 0x200,
+            instruction: 0x2200,
+        };
+        assert_eq!(exec(&mut chip8, 1), Err(error), "{}", name);
+    }
+}
+
+#[test]
+fn jp_1nnn() {
+    for (name, quirks) in presets() {
+        let mut chip8 = machine(quirks, &[0x1208]);
+        exec(&mut chip8, 1).unwrap();
+        assert_eq!(chip8.cpu.pc, 0x208, "{}", name);
+    }
+}
+
+#[test]
+fn skips_3xkk_4xkk_5xy0_9xy0() {
+    // V0 = 5, V1 = 5, V2 = 6, then each skip is tested once taken and once not taken
+    let cases = [
+        (0x3005, true),
+        (0x3006, false),
+        (0x4006, true),
+        (0x4005, false),
+        (0x5010, true),
+        (0x5020, false),
+        (0x9020, true),
+        (0x9010, false),
+    ];
+    for (name, quirks) in presets() {
+        for (opcode, taken) in cases.iter() {
+            let mut chip8 = machine(quirks, &[0x6005, 0x6105, 0x6206, *opcode]);
+            exec(&mut chip8, 4).unwrap();
+            let expected = if *taken { 0x20A } else { 0x208 };
+            assert_eq!(chip8.cpu.pc, expected, "{} {:04X}", name, opcode);
+        }
+    }
+}
+
+#[test]
+fn ld_6xkk_and_add_7xkk() {
+    for (name, quirks) in presets() {
+        let mut chip8 = machine(quirks, &[0x60FF, 0x7002]);
+        exec(&mut chip8, 1).unwrap();
+        assert_eq!(chip8.cpu.reg[0], 0xFF, "{}", name);
+        // Wraps without touching the carry flag
+        exec(&mut chip8, 1).unwrap();
+        assert_eq!(chip8.cpu.reg[0], 0x01, "{}", name);
+        assert_eq!(chip8.cpu.reg[0xF], 0, "{}", name);
+    }
+}
+
+#[test]
+fn logic_8xy0_8xy1_8xy2_8xy3() {
+    let cases = [
+        (0x8010, 0b0101),
+        (0x8011, 0b1101),
+        (0x8012, 0b0100),
+        (0x8013, 0b1001),
+    ];
+    for (name, quirks) in presets() {
+        for (opcode, result) in cases.iter() {
+            let mut chip8 = machine(quirks, &[0x600C, 0x6105, 0x6F07, *opcode]);
+            exec(&mut chip8, 4).unwrap();
+            assert_eq!(chip8.cpu.reg[0], *result, "{} {:04X}", name, opcode);
+            // 8xy0 never touches VF, the others reset it with the vf_reset quirk
+            let vf = if quirks.vf_reset && *opcode != 0x8010 {
+                0
+            } else {
+                7
+            };
+            assert_eq!(chip8.cpu.reg[0xF], vf, "{} {:04X}", name, opcode);
+        }
+    }
+}
+
+#[test]
+fn arithmetic_8xy4_8xy5_8xy7() {
+    // V0, V1, opcode, result, VF
+    let cases = [
+        (0xF0, 0x20, 0x8014, 0x10, 1),
+        (0x10, 0x20, 0x8014, 0x30, 0),
+        (0x30, 0x20, 0x8015, 0x10, 1),
+        (0x20, 0x30, 0x8015, 0xF0, 0),
+        (0x20, 0x30, 0x8017, 0x10, 1),
+        (0x30, 0x20, 0x8017, 0xF0, 0),
+    ];
+    for (name, quirks) in presets() {
+        for (v0, v1, opcode, result, vf) in cases.iter() {
+            let mut chip8 = machine(quirks, &[0x6000 | v0, 0x6100 | v1, *opcode]);
+            exec(&mut chip8, 3).unwrap();
+            assert_eq!(chip8.cpu.reg[0], *result, "{} {:04X}", name, opcode);
+            assert_eq!(chip8.cpu.reg[0xF], *vf, "{} {:04X}", name, opcode);
+        }
+
+        // The flag is written after the result when VF is the destination
+        let mut chip8 = machine(quirks, &[0x6FF0, 0x6120, 0x8F14]);
+        exec(&mut chip8, 3).unwrap();
+        assert_eq!(chip8.cpu.reg[0xF], 1, "{}", name);
+    }
+}
+
+#[test]
+fn shifts_8xy6_8xye() {
+    for (name, quirks) in presets() {
+        // V0 = 0x81, V1 = 0x42
+        let mut chip8 = machine(quirks, &[0x6081, 0x6142, 0x8016]);
+        exec(&mut chip8, 3).unwrap();
+        let (result, vf) = if quirks.shift_vy {
+            (0x21, 0)
+        } else {
+            (0x40, 1)
+        };
+        assert_eq!(chip8.cpu.reg[0], result, "{} 8XY6", name);
+        assert_eq!(chip8.cpu.reg[0xF], vf, "{} 8XY6", name);
+
+        let mut chip8 = machine(quirks, &[0x6081, 0x6142, 0x801E]);
+        exec(&mut chip8, 3).unwrap();
+        let (result, vf) = if quirks.shift_vy {
+            (0x84, 0)
+        } else {
+            (0x02, 1)
+        };
+        assert_eq!(chip8.cpu.reg[0], result, "{} 8XYE", name);
+        assert_eq!(chip8.cpu.reg[0xF], vf, "{} 8XYE", name);
+    }
+}
+
+#[test]
+fn ld_i_annn_and_add_i_fx1e() {
+    for (name, quirks) in presets() {
+        let mut chip8 = machine(quirks, &[0xA123, 0x6005, 0xF01E]);
+        exec(&mut chip8, 1).unwrap();
+        assert_eq!(chip8.cpu.i(), 0x123, "{}", name);
+        exec(&mut chip8, 2).unwrap();
+        assert_eq!(chip8.cpu.i(), 0x128, "{}", name);
+    }
+}
+
+#[test]
+fn jp_v0_bnnn() {
+    for (name, quirks) in presets() {
+        let mut chip8 = machine(quirks, &[0x6004, 0x6208, 0xB210]);
+        exec(&mut chip8, 3).unwrap();
+        let expected = if quirks.jump_vx { 0x218 } else { 0x214 };
+        assert_eq!(chip8.cpu.pc, expected, "{}", name);
+    }
+}
+
+#[test]
+fn rnd_cxkk() {
+    for (name, quirks) in presets() {
+        let mut chip8 = machine(quirks, &[0xC00F, 0xC1FF, 0xC200, 0x1200]);
+        let mut seen = [false; 256];
+        let mut values = Vec::new();
+        for _ in 0..2000 {
+            exec(&mut chip8, 4).unwrap();
+            assert!(chip8.cpu.reg[0] <= 0x0F, "{}", name);
+            assert_eq!(chip8.cpu.reg[2], 0, "{}", name);
+            seen[chip8.cpu.reg[1] as usize] = true;
+            values.push(chip8.cpu.reg[1]);
+        }
+        assert!(seen.iter().all(|s| *s), "{} doesn't cover 0..=255", name);
+
+        // The same seed gives the same numbers
+        let mut again = machine(quirks, &[0xC00F, 0xC1FF, 0xC200, 0x1200]);
+        for value in values.iter().take(100) {
+            exec(&mut again, 4).unwrap();
+            assert_eq!(again.cpu.reg[1], *value, "{}", name);
+        }
+    }
+}
+
+#[test]
+fn drw_dxyn_collision() {
+    for (name, quirks) in presets() {
+        let mut chip8 = machine(quirks, &[0xA000, 0xD005, 0xD005]);
+        exec(&mut chip8, 2).unwrap();
+        assert_eq!(chip8.cpu.reg[0xF], 0, "{}", name);
+        assert_eq!(chip8.bus.display.framebuffer[0][0], 1, "{}", name);
+        exec(&mut chip8, 1).unwrap();
+        assert_eq!(chip8.cpu.reg[0xF], 1, "{}", name);
+        assert_eq!(lit_pixels(&chip8), 0, "{}", name);
+    }
+}
+
+#[test]
+fn drw_dxyn_waits_for_vblank() {
+    for (name, quirks) in presets() {
+        let mut chip8 = machine(quirks, &[0xA000, 0xD005, 0x6001]);
+        exec(&mut chip8, 1).unwrap();
+        let clock = chip8.clock;
+        exec(&mut chip8, 1).unwrap();
+        let tick = chip8.frame * 1_000_000 / 60;
+        if quirks.display_wait {
+            // The next instruction starts on the tick after the draw
+            assert_eq!((chip8.frame, chip8.clock), (1, tick), "{}", name);
+        } else {
+            assert_eq!(chip8.clock, clock + 22743, "{}", name);
+            assert_ne!(chip8.clock, tick, "{}", name);
+        }
+    }
+}
+
+#[test]
+fn drw_dxyn_wraps_or_clips() {
+    for (name, quirks) in presets() {
+        // The 0 glyph is 4 pixels wide, drawn 2 pixels from the right edge
+        let mut chip8 = machine(quirks, &[0xA000, 0x603E, 0x6100, 0xD015]);
+        exec(&mut chip8, 4).unwrap();
+        assert_eq!(chip8.bus.display.framebuffer[0][63], 1, "{}", name);
+        let wrapped = if quirks.clip_sprites { 0 } else { 1 };
+        assert_eq!(chip8.bus.display.framebuffer[0][0], wrapped, "{}", name);
+
+        // The origin always wraps
+        let mut chip8 = machine(quirks, &[0xA000, 0x6041, 0x6121, 0xD015]);
+        exec(&mut chip8, 4).unwrap();
+        assert_eq!(chip8.bus.display.framebuffer[1][1], 1, "{}", name);
+    }
+}
+
+#[test]
+fn key_skips_ex9e_exa1() {
+    for (name, quirks) in presets() {
+        for (opcode, pressed, taken) in [
+            (0xE59E, true, true),
+            (0xE59E, false, false),
+            (0xE5A1, true, false),
+            (0xE5A1, false, true),
+        ]
+        .iter()
+        {
+            let mut chip8 = machine(quirks, &[0x6507, *opcode]);
+            chip8.set_key(7, *pressed);
+            exec(&mut chip8, 2).unwrap();
+            let expected = if *taken { 0x206 } else { 0x204 };
+            assert_eq!(chip8.cpu.pc, expected, "{} {:04X}", name, opcode);
+        }
+    }
+}
+
+#[test]
+fn timers_fx07_fx15_fx18() {
+    for (name, quirks) in presets() {
+        let mut chip8 = machine(quirks, &[0x6A0A, 0xFA15, 0xFA18, 0xFB07, 0x1208]);
+        exec(&mut chip8, 4).unwrap();
+        assert_eq!(chip8.cpu.reg[0xB], 10, "{}", name);
+        assert!(chip8.sound_active(), "{}", name);
+        chip8.run_frame().unwrap();
+        assert_eq!((chip8.cpu.dt, chip8.cpu.st), (9, 9), "{}", name);
+        for _ in 0..9 {
+            chip8.run_frame().unwrap();
+        }
+        assert_eq!((chip8.cpu.dt, chip8.cpu.st), (0, 0), "{}", name);
+        assert!(!chip8.sound_active(), "{}", name);
+    }
+}
+
+#[test]
+fn wait_for_key_fx0a() {
+    for (name, quirks) in presets() {
+        let mut chip8 = machine(quirks, &[0xF30A, 0x6001]);
+        exec(&mut chip8, 1).unwrap();
+        for _ in 0..3 {
+            chip8.run_frame().unwrap();
+        }
+        assert_eq!(chip8.cpu.pc, 0x202, "{}", name);
+        assert_eq!(chip8.cpu.reg[0], 0, "{}", name);
+
+        // The wait ends when the key is released, the timers keep running meanwhile
+        let frame = chip8.frame;
+        chip8.set_key(7, true);
+        chip8.run_frame().unwrap();
+        assert!(chip8.cpu.wait_for_input, "{}", name);
+        assert_eq!(chip8.frame, frame + 1, "{}", name);
+        chip8.set_key(7, false);
+        exec(&mut chip8, 1).unwrap();
+        assert_eq!(chip8.cpu.reg[3], 7, "{}", name);
+        assert_eq!(chip8.cpu.reg[0], 1, "{}", name);
+    }
+}
+
+#[test]
+fn wait_for_key_fx0a_has_its_own_execution_time() {
+    for (name, quirks) in presets() {
+        // A draw right before the wait doesn't add its time twice
+        let mut chip8 = machine(quirks, &[0xD005, 0xF30A]);
+        exec(&mut chip8, 1).unwrap();
+        let (clock, frame) = (chip8.clock, chip8.frame);
+        exec(&mut chip8, 1).unwrap();
+        assert!(chip8.cpu.wait_for_input, "{}", name);
+        assert_eq!((chip8.clock, chip8.frame), (clock + 45, frame), "{}", name);
+    }
+}
+
+#[test]
+fn wait_for_key_fx0a_ignores_held_keys() {
+    for (name, quirks) in presets() {
+        let mut chip8 = machine(quirks, &[0xF30A, 0xF40A, 0x6001]);
+        chip8.set_key(2, true);
+        exec(&mut chip8, 1).unwrap();
+        // Released without a press since the wait started
+        chip8.set_key(2, false);
+        chip8.run_frame().unwrap();
+        assert!(chip8.cpu.wait_for_input, "{}", name);
+
+        chip8.set_key(2, true);
+        chip8.set_key(5, true);
+        chip8.set_key(5, false);
+        chip8.set_key(2, false);
+        assert_eq!(chip8.cpu.reg[3], 2, "{}", name);
+
+        // Keys still held don't end the next wait
+        chip8.set_key(5, true);
+        exec(&mut chip8, 1).unwrap();
+        chip8.set_key(6, true);
+        chip8.run_frame().unwrap();
+        assert!(chip8.cpu.wait_for_input, "{}", name);
+        chip8.set_key(5, false);
+        chip8.run_frame().unwrap();
+        assert!(chip8.cpu.wait_for_input, "{}", name);
+        chip8.set_key(6, false);
+        exec(&mut chip8, 1).unwrap();
+        assert_eq!(chip8.cpu.reg[4], 6, "{}", name);
+        assert_eq!(chip8.cpu.reg[0], 1, "{}", name);
+    }
+}
+
+#[test]
+fn key_edges_reset_every_frame() {
+    let mut chip8 = machine(Quirks::default(), &[0x1200]);
+    chip8.set_key(3, true);
+    chip8.set_keys(0b1001);
+    assert_eq!(chip8.bus.keyboard.pressed, 0b1001);
+    assert_eq!(chip8.bus.keyboard.released, 0);
+    chip8.set_key(3, false);
+    assert_eq!(chip8.bus.keyboard.released, 0b1000);
+
+    // The edges are part of save states
+    let state = chip8.save_state();
+    chip8.run_frame().unwrap();
+    assert_eq!(chip8.bus.keyboard.pressed, 0);
+    assert_eq!(chip8.bus.keyboard.released, 0);
+    chip8.load_state(&state).unwrap();
+    assert_eq!(chip8.bus.keyboard.pressed, 0b1001);
+    assert_eq!(chip8.bus.keyboard.released, 0b1000);
+}
+
+#[test]
+fn fonts_fx29_fx30() {
+    for (name, quirks) in presets() {
+        let mut chip8 = machine(quirks, &[0x6005, 0xF029, 0xF030]);
+        exec(&mut chip8, 2).unwrap();
+        assert_eq!(chip8.cpu.i(), 25, "{}", name);
+        exec(&mut chip8, 1).unwrap();
+        assert_eq!(chip8.cpu.i(), 0x50 + 50, "{}", name);
+    }
+}
+
+#[test]
+fn bcd_fx33() {
+    for (name, quirks) in presets() {
+        let mut chip8 = machine(quirks, &[0x60EA, 0xA300, 0xF033]);
+        exec(&mut chip8, 3).unwrap();
+        assert_eq!(&chip8.bus.ram.mem[0x300..0x303], &[2, 3, 4], "{}", name);
+        assert_eq!(chip8.cpu.i(), 0x300, "{}", name);
+    }
+}
+
+#[test]
+fn store_fx55_and_load_fx65() {
+    for (name, quirks) in presets() {
+        let program = [0x6001, 0x6102, 0x6203, 0xA300, 0xF255];
+        let mut chip8 = machine(quirks, &program);
+        exec(&mut chip8, 5).unwrap();
+        assert_eq!(&chip8.bus.ram.mem[0x300..0x304], &[1, 2, 3, 0], "{}", name);
+        let i = if quirks.load_store_inc_i {
+            0x303
+        } else {
+            0x300
+        };
+        assert_eq!(chip8.cpu.i(), i, "{}", name);
+
+        let mut chip8 = machine(quirks, &[0xA300, 0xF165]);
+        chip8.bus.ram.mem[0x300..0x302].copy_from_slice(&[7, 8]);
+        exec(&mut chip8, 2).unwrap();
+        assert_eq!(&chip8.cpu.reg[0..3], &[7, 8, 0], "{}", name);
+        let i = if quirks.load_store_inc_i {
+            0x302
+        } else {
+            0x300
+        };
+        assert_eq!(chip8.cpu.i(), i, "{}", name);
+    }
+}
+
+#[test]
+fn store_fx55_out_of_bounds_fails() {
+    for (name, quirks) in presets() {
+        let mut chip8 = machine(quirks, &[0xAFFF, 0xF155]);
+        let result = exec(&mut chip8, 2);
+        if quirks.xo_chip {
+            // XO-CHIP has 64 KiB of memory
+            assert_eq!(result, Ok(()), "{}", name);
+        } else {
+            let error = Chip8Error::MemoryOutOfBounds {
+                pc: 0x202,
+                instruction: 0xF155,
+                address: 0x1000,
+            };
+            assert_eq!(result, Err(error), "{}", name);
+        }
+    }
+}
+
+#[test]
+fn rpl_flags_fx75_fx85() {
+    for (name, quirks) in presets() {
+        let mut chip8 = machine(quirks, &[0x6001, 0x6102, 0xF175, 0x6000, 0x6100, 0xF185]);
+        exec(&mut chip8, 3).unwrap();
+        assert_eq!(&chip8.cpu.rpl[0..3], &[1, 2, 0], "{}", name);
+        exec(&mut chip8, 3).unwrap();
+        assert_eq!(&chip8.cpu.reg[0..2], &[1, 2], "{}", name);
+    }
+}
+
+#[test]
+fn resolution_00fe_00ff() {
+    for (name, quirks) in presets() {
+        let mut chip8 = machine(quirks, &[0xA000, 0xD005, 0x00FF, 0x00FE]);
+        exec(&mut chip8, 3).unwrap();
+        assert!(chip8.bus.display.hires, "{}", name);
+        assert_eq!(chip8.bus.display.width(), 128, "{}", name);
+        // Switching resolution clears the screen
+        assert_eq!(lit_pixels(&chip8), 0, "{}", name);
+        exec(&mut chip8, 1).unwrap();
+        assert!(!chip8.bus.display.hires, "{}", name);
+        assert_eq!(chip8.bus.display.width(), 64, "{}", name);
+    }
+}
+
+#[test]
+fn large_sprite_dxy0() {
+    for (name, quirks) in presets() {
+        // 32 bytes of 0xFF follow the code
+        let mut program = vec![0x00FF, 0xA208, 0xD000, 0x1206];
+        program.extend_from_slice(&[0xFFFF; 16]);
+        let mut chip8 = machine(quirks, &program);
+        exec(&mut chip8, 3).unwrap();
+        assert_eq!(lit_pixels(&chip8), 256, "{}", name);
+        assert_eq!(chip8.bus.display.framebuffer[15][15], 1, "{}", name);
+    }
+}
+
+#[test]
+fn scrolls_00cn_00fb_00fc() {
+    for (name, quirks) in presets() {
+        // A single pixel at the origin, the sprite byte is at 0x20A
+        let program = [0xA20A, 0xD001, 0x00C2, 0x00FB, 0x00FC, 0x8000];
+        let mut chip8 = machine(quirks, &program);
+        exec(&mut chip8, 3).unwrap();
+        assert_eq!(chip8.bus.display.framebuffer[2][0], 1, "{}", name);
+        exec(&mut chip8, 1).unwrap();
+        assert_eq!(chip8.bus.display.framebuffer[2][4], 1, "{}", name);
+        exec(&mut chip8, 1).unwrap();
+        assert_eq!(chip8.bus.display.framebuffer[2][0], 1, "{}", name);
+        assert_eq!(lit_pixels(&chip8), 1, "{}", name);
+    }
+}
+
+#[test]
+fn exit_00fd() {
+    for (name, quirks) in presets() {
+        let mut chip8 = machine(quirks, &[0x00FD, 0x6001]);
+        let clock = chip8.clock;
+        exec(&mut chip8, 1).unwrap();
+        assert!(chip8.cpu.halted, "{}", name);
+        assert_eq!(chip8.clock, clock + 109, "{}", name);
+        assert_eq!(chip8.cpu.pc, 0x200, "{}", name);
+        chip8.run_frame().unwrap();
+        assert_eq!(chip8.cpu.reg[0], 0, "{}", name);
+    }
+}
+
+#[test]
+fn step_runs_one_instruction() {
+    let mut chip8 = machine(Quirks::default(), &[0x6005, 0x7001]);
+    chip8.step().unwrap();
+    assert_eq!((chip8.cpu.pc, chip8.cpu.reg[0]), (0x202, 5));
+    chip8.step().unwrap();
+    assert_eq!((chip8.cpu.pc, chip8.cpu.reg[0]), (0x204, 6));
+    assert_eq!(chip8.instructions, 2);
+}
+
+#[test]
+fn xo_chip_opcodes() {
+    for (name, quirks) in presets() {
+        let opcodes = [
+            [0xF000, 0x1234],
+            [0x5012, 0x0000],
+            [0x5013, 0x0000],
+            [0xF201, 0x0000],
+            [0x00D1, 0x0000],
+            [0xF002, 0x0000],
+            [0xF13A, 0x0000],
+        ];
+        if !quirks.xo_chip {
+            for [opcode, next] in opcodes.iter() {
+                let mut chip8 = machine(quirks, &[*opcode, *next]);
+                let error = Chip8Error::UnknownOpcode {
+                    pc: 0x200,
+                    instruction: *opcode,
+                };
+                assert_eq!(exec(&mut chip8, 1), Err(error), "{}", name);
+            }
+            continue;
+        }
+
+        // i := long 0x1234
+        let mut chip8 = machine(quirks, &[0xF000, 0x1234]);
+        exec(&mut chip8, 1).unwrap();
+        assert_eq!(chip8.cpu.i(), 0x1234, "{}", name);
+        assert_eq!(chip8.cpu.pc, 0x204, "{}", name);
+
+        // save v1 - v3, then load v3 - v1 reverses them
+        let program = [0x6101, 0x6202, 0x6303, 0xA300, 0x5132, 0x5313];
+        let mut chip8 = machine(quirks, &program);
+        exec(&mut chip8, 5).unwrap();
+        assert_eq!(&chip8.bus.ram.mem[0x300..0x303], &[1, 2, 3], "{}", name);
+        assert_eq!(chip8.cpu.i(), 0x300, "{}", name);
+        exec(&mut chip8, 1).unwrap();
+        assert_eq!(&chip8.cpu.reg[1..4], &[3, 2, 1], "{}", name);
+
+        // plane 2 then scroll-up 1 only moves the second plane
+        let program = [
+            0xA20E, 0x6000, 0x6101, 0xD011, 0xF201, 0xD011, 0x00D1, 0x8000,
+        ];
+        let mut chip8 = machine(quirks, &program);
+        exec(&mut chip8, 5).unwrap();
+        assert_eq!(chip8.bus.display.planes, 2, "{}", name);
+        exec(&mut chip8, 2).unwrap();
+        assert_eq!(chip8.bus.display.framebuffer[1][0], 1, "{}", name);
+        assert_eq!(chip8.bus.display.framebuffer[0][0], 2, "{}", name);
+
+        // audio loads the pattern at I, pitch := v1
+        let mut chip8 = machine(quirks, &[0xA300, 0xF002, 0x6150, 0xF13A]);
+        chip8.bus.ram.mem[0x300..0x310].copy_from_slice(&[0xAA; 16]);
+        exec(&mut chip8, 2).unwrap();
+        assert_eq!(chip8.cpu.audio_pattern, Some([0xAA; 16]), "{}", name);
+        assert_eq!(chip8.cpu.pitch, 64, "{}", name);
+        exec(&mut chip8, 2).unwrap();
+        assert_eq!(chip8.cpu.pitch, 0x50, "{}", name);
+    }
+}
+
+#[test]
+fn xo_chip_skips_over_long_load() {
+    for (name, quirks) in presets().into_iter().filter(|(_, q)| q.xo_chip) {
+        let mut chip8 = machine(quirks, &[0x3000, 0xF000, 0x1234, 0x6001]);
+        exec(&mut chip8, 2).unwrap();
+        assert_eq!(chip8.cpu.reg[0], 1, "{}", name);
+        assert_eq!(chip8.cpu.i(), 0, "{}", name);
+    }
+}
+
+#[test]
+fn unknown_opcode_reports_pc() {
+    for (name, quirks) in presets() {
+        let mut chip8 = machine(quirks, &[0x6000, 0x5001]);
+        let error = Chip8Error::UnknownOpcode {
+            pc: 0x202,
+            instruction: 0x5001,
+        };
+        assert_eq!(exec(&mut chip8, 2), Err(error), "{}", name);
+    }
+}
@@ -0,0 +1,63 @@
+// Built-in and custom color palettes
+use chip8::display::Display;
+use chip8::palette::{self, parse_color, Palette};
+
+#[test]
+fn colors_parse_with_or_without_hash() {
+    assert_eq!(parse_color("#FFB000"), Some([0xFF, 0xB0, 0x00, 0xFF]));
+    assert_eq!(parse_color("0f380f"), Some([0x0F, 0x38, 0x0F, 0xFF]));
+    assert_eq!(parse_color("#FFF"), None);
+    assert_eq!(parse_color("#GGGGGG"), None);
+    assert_eq!(parse_color("#+12345"), None);
+    assert_eq!(parse_color("-12345"), None);
+}
+
+#[test]
+fn two_colors_fill_every_plane_with_the_foreground() {
+    let palette = Palette::parse("paper", &["#FFFFFF", "#000000"]).unwrap();
+    assert_eq!(palette.colors[0], [0xFF, 0xFF, 0xFF, 0xFF]);
+    for color in &palette.colors[1..] {
+        assert_eq!(*color, [0, 0, 0, 0xFF]);
+    }
+}
+
+#[test]
+fn palettes_need_two_or_four_valid_colors() {
+    assert!(Palette::parse("a", &["#000000"]).is_err());
+    assert!(Palette::parse("a", &["#000000", "#111111", "#222222"]).is_err());
+    assert!(Palette::parse("a", &["#000000", "red"]).is_err());
+    let four = ["#000000", "#111111", "#222222", "#333333"];
+    assert_eq!(
+        Palette::parse("a", &four).unwrap().colors[3],
+        [0x33, 0x33, 0x33, 0xFF]
+    );
+}
+
+#[test]
+fn builtin_palettes_start_with_classic() {
+    let names: Vec<String> = palette::builtin().into_iter().map(|p| p.name).collect();
+    assert_eq!(
+        names,
+        [
+            "classic",
+            "amber",
+            "green",
+            "lcd",
+            "high-contrast",
+            "colorblind"
+        ]
+    );
+    assert_eq!(Palette::default().colors[0], [0, 0, 0, 0xFF]);
+    assert_eq!(Palette::default().colors[1], [0xFF, 0xFF, 0xFF, 0xFF]);
+}
+
+#[test]
+fn rgba_output_uses_the_palette() {
+    let mut display = Display::new();
+    display.draw_s(0, 0, &[0x80], true);
+    let amber = palette::builtin().remove(1);
+    let rgba = display.to_rgba(1, &amber.colors);
+    assert_eq!(rgba.len(), 64 * 32 * 4);
+    assert_eq!(&rgba[..4], &amber.colors[1]);
+    assert_eq!(&rgba[4..8], &amber.colors[0]);
+}
@@ -0,0 +1,87 @@
+// Frame blending for flickering games
+use chip8::display::Display;
+use chip8::palette::Palette;
+use chip8::phosphor::Phosphor;
+
+const WHITE: [u8; 4] = [0xFF, 0xFF, 0xFF, 0xFF];
+const BLACK: [u8; 4] = [0, 0, 0, 0xFF];
+
+fn lit() -> Display {
+    let mut display = Display::new();
+    display.draw_s(0, 0, &[0x80], true);
+    display
+}
+
+#[test]
+fn lit_pixels_show_at_once() {
+    let colors = Palette::default().colors;
+    let mut phosphor = Phosphor::new(0.5);
+    phosphor.reset(&Display::new(), &colors);
+    phosphor.update(&lit(), &colors);
+    assert_eq!(phosphor.pixel(0, 0), WHITE);
+    assert_eq!(phosphor.pixel(1, 0), BLACK);
+}
+
+#[test]
+fn dark_pixels_fade_to_the_background() {
+    let colors = Palette::default().colors;
+    let mut phosphor = Phosphor::new(0.5);
+    phosphor.reset(&lit(), &colors);
+    let blank = Display::new();
+    phosphor.update(&blank, &colors);
+    assert_eq!(phosphor.pixel(0, 0), [0x80, 0x80, 0x80, 0xFF]);
+    phosphor.update(&blank, &colors);
+    assert_eq!(phosphor.pixel(0, 0), [0x40, 0x40, 0x40, 0xFF]);
+    for _ in 0..20 {
+        phosphor.update(&blank, &colors);
+    }
+    assert_eq!(phosphor.pixel(0, 0), BLACK);
+}
+
+#[test]
+fn flicker_stays_visible() {
+    let colors = Palette::default().colors;
+    let mut phosphor = Phosphor::new(0.75);
+    phosphor.reset(&Display::new(), &colors);
+    // A sprite erased and redrawn on alternate frames
+    for frame in 0..10 {
+        let display = if frame % 2 == 0 {
+            lit()
+        } else {
+            Display::new()
+        };
+        phosphor.update(&display, &colors);
+        assert!(phosphor.pixel(0, 0)[0] >= 0xBF);
+    }
+}
+
+#[test]
+fn no_decay_shows_every_frame_as_it_is() {
+    let colors = Palette::default().colors;
+    let mut phosphor = Phosphor::new(0.0);
+    phosphor.reset(&lit(), &colors);
+    phosphor.update(&Display::new(), &colors);
+    assert_eq!(phosphor.pixel(0, 0), BLACK);
+}
+
+#[test]
+fn blending_leaves_the_display_alone() {
+    let colors = Palette::default().colors;
+    let mut phosphor = Phosphor::new(0.9);
+    let display = lit();
+    let hash = display.hash();
+    phosphor.reset(&display, &colors);
+    phosphor.update(&display, &colors);
+    assert_eq!(display.hash(), hash);
+}
+
+#[test]
+fn resolution_changes_drop_the_history() {
+    let colors = Palette::default().colors;
+    let mut phosphor = Phosphor::new(0.9);
+    phosphor.reset(&lit(), &colors);
+    let mut hires = Display::new();
+    hires.hires = true;
+    phosphor.update(&hires, &colors);
+    assert_eq!(phosphor.pixel(0, 0), BLACK);
+}
@@ -0,0 +1,96 @@
+// The rewind buffer stores older frames as deltas against the newer ones
+use chip8::rewind::Rewind;
+use chip8::{Chip8, Quirks};
+
+fn pushed(states: &[Vec<u8>], capacity: usize) -> Rewind {
+    let mut rewind = Rewind::new(capacity);
+    for state in states {
+        rewind.push(state.clone());
+    }
+    rewind
+}
+
+// Pops every state back and checks they come out newest first
+fn assert_rewinds(states: &[Vec<u8>]) {
+    let mut rewind = pushed(states, states.len());
+    assert_eq!(rewind.len(), states.len() - 1);
+    for state in states.iter().rev().skip(1) {
+        assert_eq!(rewind.pop().as_ref(), Some(state));
+    }
+    assert_eq!(rewind.pop(), None);
+    assert!(rewind.is_empty());
+}
+
+#[test]
+fn identical_states_round_trip() {
+    assert_rewinds(&[vec![0; 5000], vec![0; 5000], vec![0; 5000]]);
+    assert_rewinds(&[vec![0xAB; 300], vec![0xAB; 300]]);
+}
+
+#[test]
+fn long_runs_round_trip() {
+    // Runs of zero and literal bytes longer than one and two varint bytes hold
+    let mut a = vec![0; 40000];
+    let mut b = a.clone();
+    for byte in b[100..300].iter_mut() {
+        *byte = 1;
+    }
+    b[20000] = 0xFF;
+    for byte in b[30000..39999].iter_mut() {
+        *byte = 0x55;
+    }
+    a[39999] = 7;
+    assert_rewinds(&[a.clone(), b.clone(), a, b]);
+}
+
+#[test]
+fn completely_different_states_round_trip() {
+    let a: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+    let b: Vec<u8> = a.iter().map(|byte| !byte).collect();
+    let c: Vec<u8> = a.iter().map(|byte| byte.wrapping_mul(3) | 1).collect();
+    assert_rewinds(&[a, b, c]);
+}
+
+#[test]
+fn size_changes_round_trip() {
+    assert_rewinds(&[vec![1; 10], vec![2; 20], vec![3; 20], vec![4; 5]]);
+}
+
+#[test]
+fn capacity_drops_the_oldest_frames() {
+    let states: Vec<Vec<u8>> = (0..10u8).map(|i| vec![i; 64]).collect();
+    let mut rewind = pushed(&states, 3);
+    assert_eq!(rewind.len(), 3);
+    for i in (6..9u8).rev() {
+        assert_eq!(rewind.pop(), Some(vec![i; 64]));
+    }
+    assert_eq!(rewind.pop(), None);
+}
+
+#[test]
+fn clear_forgets_everything() {
+    let mut rewind = pushed(&[vec![1; 8], vec![2; 8]], 4);
+    rewind.clear();
+    assert_eq!(rewind.pop(), None);
+    rewind.push(vec![3; 8]);
+    assert_eq!(rewind.pop(), None);
+}
+
+#[test]
+fn machine_states_rewind_frame_by_frame() {
+    let rom = std::fs::read("games/PONG").unwrap();
+    let mut chip8 = Chip8::new(Quirks::default());
+    chip8.load_rom(&rom).unwrap();
+    let mut rewind = Rewind::new(600);
+    let mut states = Vec::new();
+    for _ in 0..120 {
+        chip8.run_frame().unwrap();
+        states.push(chip8.save_state());
+        rewind.push(chip8.save_state());
+    }
+    for state in states.iter().rev().skip(1) {
+        let previous = rewind.pop().unwrap();
+        assert_eq!(&previous, state);
+        chip8.load_state(&previous).unwrap();
+    }
+}
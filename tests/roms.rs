@@ -0,0 +1,156 @@
+// Runs the bundled test roms headless and reads their verdict off the screen
+mod common;
+
+use chip8::{Chip8, Quirks};
+use common::presets;
+
+const BON: [&str; 8] = [
+    "####.....####...#....#",
+    "#...#...#....#..##...#",
+    "#...#...#....#..#.#..#",
+    "####....#....#..#..#.#",
+    "#...#...#....#..#...##",
+    "#...#...#....#..#....#",
+    "#...#...#....#..#....#",
+    "####.....####...#....#",
+];
+
+// The OK printed by c8_test once every test passed
+const C8_OK: [&str; 5] = [
+    ".##....#..#",
+    "#..#...#.#.",
+    "#..#...##..",
+    "#..#...#.#.",
+    ".##....#..#",
+];
+
+// Error numbers of BC_test, its E is drawn further left
+const BC_E12: [&str; 5] = [
+    ".#...####",
+    "##......#",
+    ".#...####",
+    ".#...#...",
+    "###..####",
+];
+const BC_E16: [&str; 5] = [
+    ".#...####",
+    "##...#...",
+    ".#...####",
+    ".#...#..#",
+    "###..####",
+];
+
+// Numbers of the first failing c8_test test
+const C8_14: [&str; 5] = [
+    ".#....#..#",
+    "##....#..#",
+    ".#....####",
+    ".#.......#",
+    "###......#",
+];
+
+// The OK printed by test_opcode next to each passing opcode
+const OPCODE_OK: [&str; 4] = ["###.#.#", "#.#.##.", "#.#.#.#", "###.#.#"];
+
+fn run(path: &str, quirks: Quirks, frames: u64) -> Chip8 {
+    let rom = std::fs::read(path).unwrap();
+    let mut chip8 = Chip8::new(quirks);
+    chip8.seed(0);
+    chip8.load_rom(&rom).unwrap();
+    for _ in 0..frames {
+        chip8.run_frame().unwrap();
+    }
+    chip8
+}
+
+// Number of places the glyph appears on the screen
+fn count_glyph(chip8: &Chip8, glyph: &[&str]) -> usize {
+    let screen = chip8.bus.display.to_text();
+    let lines: Vec<&str> = screen.lines().collect();
+    let width = glyph[0].len();
+    let mut count = 0;
+    for y in 0..lines.len().saturating_sub(glyph.len() - 1) {
+        for x in 0..lines[y].len().saturating_sub(width - 1) {
+            if glyph
+                .iter()
+                .enumerate()
+                .all(|(row, pattern)| &lines[y + row][x..x + width] == *pattern)
+            {
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+#[test]
+fn bc_test_shows_bon() {
+    // Written for interpreters that shift Vx in place and leave I unchanged, the presets
+    // that don't stop at the first test relying on that
+    for (name, quirks) in presets() {
+        let chip8 = run("tests/BC_test.ch8", quirks, 300);
+        let verdict: &[&str] = if quirks.shift_vy {
+            // 8XYE shifts Vy into Vx
+            &BC_E12
+        } else if quirks.load_store_inc_i {
+            // Fx55 and Fx65 move I past the stored registers
+            &BC_E16
+        } else {
+            &BON
+        };
+        assert_eq!(
+            count_glyph(&chip8, verdict),
+            1,
+            "{}\n{}",
+            name,
+            chip8.bus.display.to_text()
+        );
+    }
+}
+
+#[test]
+fn test_opcode_passes_every_opcode() {
+    for (name, quirks) in presets() {
+        let chip8 = run("tests/test_opcode.ch8", quirks, 300);
+        assert_eq!(
+            count_glyph(&chip8, &OPCODE_OK),
+            18,
+            "{}\n{}",
+            name,
+            chip8.bus.display.to_text()
+        );
+    }
+}
+
+#[test]
+fn c8_test_shows_ok() {
+    // Written for interpreters that add V0 in Bnnn and leave I alone in Fx55 and Fx65, the
+    // other presets stop at the first test relying on that. The shift tests pass either way,
+    // they shift V0 which holds the same value.
+    for (name, quirks) in presets() {
+        let chip8 = run("tests/c8_test.c8", quirks, 300);
+        let verdict: &[&str] = if quirks.jump_vx || quirks.load_store_inc_i {
+            // Bxnn adds Vx, or Fx55 and Fx65 move I past the registers. Both tests report
+            // error 14.
+            &C8_14
+        } else {
+            &C8_OK
+        };
+        assert_eq!(
+            count_glyph(&chip8, verdict),
+            1,
+            "{}\n{}",
+            name,
+            chip8.bus.display.to_text()
+        );
+    }
+}
+
+#[test]
+fn games_run_without_errors() {
+    for entry in std::fs::read_dir("games").unwrap() {
+        let path = entry.unwrap().path();
+        let chip8 = run(path.to_str().unwrap(), Quirks::default(), 600);
+        assert!(!chip8.cpu.halted, "{}", path.display());
+    }
+}
@@ -0,0 +1,77 @@
+// The headless chip8 run command
+use std::process::{Command, Output};
+
+use chip8::movie::Movie;
+use chip8::Quirks;
+
+fn chip8(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_chip8"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+// A PONG movie that holds key 1 from frame 10
+fn pong_movie(name: &str) -> String {
+    let rom = std::fs::read("games/PONG").unwrap();
+    let mut movie = Movie::new(&rom, 3, Quirks::default());
+    movie.record(10, 0b10);
+    movie.length = 60;
+    let path = std::env::temp_dir().join(format!("chip8-{}-{}.movie", name, std::process::id()));
+    std::fs::write(&path, movie.to_string()).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+#[test]
+fn movies_replay_headless() {
+    let movie = pong_movie("replay");
+    let first = chip8(&["run", "games/PONG", "--play", &movie, "--dump", "hash"]);
+    let second = chip8(&["run", "games/PONG", "--play", &movie, "--dump", "hash"]);
+    std::fs::remove_file(&movie).unwrap();
+    assert!(first.status.success());
+    assert_eq!(first.stdout, second.stdout);
+}
+
+#[test]
+fn movies_of_another_rom_are_rejected() {
+    let movie = pong_movie("other-rom");
+    let output = chip8(&["run", "games/TANK", "--play", &movie, "--dump", "hash"]);
+    std::fs::remove_file(&movie).unwrap();
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("recorded with a different rom"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn keys_cant_replace_a_movie() {
+    let movie = pong_movie("keys");
+    let output = chip8(&["run", "games/PONG", "--play", &movie, "--keys", "5:1"]);
+    std::fs::remove_file(&movie).unwrap();
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn expect_fails_on_another_screen() {
+    let output = chip8(&["run", "games/PONG", "--frames", "30", "--dump", "hash"]);
+    let hash = String::from_utf8(output.stdout).unwrap();
+    let hash = hash.trim();
+    let args = [
+        "run",
+        "games/PONG",
+        "--frames",
+        "30",
+        "--dump",
+        "hash",
+        "--expect",
+    ];
+    assert!(chip8(&[&args[..], &[hash]].concat()).status.success());
+    assert!(!chip8(&[&args[..], &["0123456789abcdef"]].concat())
+        .status
+        .success());
+}
@@ -0,0 +1,74 @@
+// Screenshots of the display as PNG files
+use std::time::{Duration, UNIX_EPOCH};
+
+use chip8::display::Display;
+use chip8::palette;
+use chip8::screenshot;
+
+#[test]
+fn file_names_carry_a_utc_timestamp() {
+    let time = UNIX_EPOCH + Duration::from_millis(1_792_327_845_042);
+    assert_eq!(
+        screenshot::file_name("PONG", time),
+        "PONG-20261018-125045-042.png"
+    );
+    let leap_day = UNIX_EPOCH + Duration::from_secs(951_782_400);
+    assert_eq!(
+        screenshot::file_name("TANK", leap_day),
+        "TANK-20000229-000000-000.png"
+    );
+}
+
+#[test]
+fn screenshots_use_the_palette_and_scale() {
+    let dir = std::env::temp_dir().join(format!("chip8-screenshot-test-{}", std::process::id()));
+    let mut display = Display::new();
+    display.draw_s(0, 0, &[0x80], true);
+    let amber = palette::builtin().remove(1);
+
+    let path = screenshot::save(&display, 1, &amber.colors, &dir, "native").unwrap();
+    let decoder = png::Decoder::new(std::fs::File::open(&path).unwrap());
+    let mut reader = decoder.read_info().unwrap();
+    let mut rgba = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut rgba).unwrap();
+    assert_eq!((info.width, info.height), (64, 32));
+    assert_eq!(&rgba[..4], &amber.colors[1]);
+    assert_eq!(&rgba[4..8], &amber.colors[0]);
+
+    display.hires = true;
+    let path = screenshot::save(&display, 3, &amber.colors, &dir, "scaled").unwrap();
+    assert!(path
+        .file_name()
+        .unwrap()
+        .to_string_lossy()
+        .starts_with("scaled-"));
+    let decoder = png::Decoder::new(std::fs::File::open(&path).unwrap());
+    let info = decoder.read_info().unwrap().info().clone();
+    assert_eq!((info.width, info.height), (384, 192));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn screenshots_in_the_same_millisecond_get_a_counter() {
+    let dir = std::env::temp_dir().join(format!("chip8-screenshot-burst-{}", std::process::id()));
+    let display = Display::new();
+    let colors = palette::builtin().remove(0).colors;
+    let time = UNIX_EPOCH + Duration::from_millis(1_792_327_845_042);
+    let names: Vec<_> = (0..3)
+        .map(|_| {
+            let path = screenshot::save_at(&display, 1, &colors, &dir, "PONG", time).unwrap();
+            path.file_name().unwrap().to_string_lossy().into_owned()
+        })
+        .collect();
+    let files = std::fs::read_dir(&dir).unwrap().count();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(
+        names,
+        [
+            "PONG-20261018-125045-042.png",
+            "PONG-20261018-125045-042-1.png",
+            "PONG-20261018-125045-042-2.png"
+        ]
+    );
+    assert_eq!(files, 3);
+}
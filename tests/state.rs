@@ -0,0 +1,90 @@
+// Save states of the whole machine
+use chip8::state::{StateError, MAGIC, VERSION};
+use chip8::{Chip8, Quirks};
+
+fn pong(frames: u64) -> Chip8 {
+    let rom = std::fs::read("games/PONG").unwrap();
+    let mut chip8 = Chip8::new(Quirks::default());
+    chip8.seed(7);
+    chip8.load_rom(&rom).unwrap();
+    for _ in 0..frames {
+        chip8.run_frame().unwrap();
+    }
+    chip8
+}
+
+#[test]
+fn states_round_trip() {
+    let mut chip8 = pong(120);
+    chip8.set_key(0x1, true);
+    let state = chip8.save_state();
+    assert_eq!(&state[..4], MAGIC);
+    assert_eq!(state[4], VERSION);
+
+    let mut reference = pong(0);
+    reference.load_state(&state).unwrap();
+    assert_eq!(reference.save_state(), state);
+    assert_eq!(reference.frame, chip8.frame);
+    assert_eq!(reference.clock, chip8.clock);
+
+    // Both machines carry on identically, random numbers included
+    for _ in 0..120 {
+        chip8.run_frame().unwrap();
+        reference.run_frame().unwrap();
+    }
+    assert_eq!(reference.save_state(), chip8.save_state());
+    assert_eq!(reference.bus.display.hash(), chip8.bus.display.hash());
+}
+
+#[test]
+fn loading_rewinds_the_machine() {
+    let mut chip8 = pong(60);
+    let state = chip8.save_state();
+    let (frame, hash) = (chip8.frame, chip8.bus.display.hash());
+    for _ in 0..60 {
+        chip8.run_frame().unwrap();
+    }
+    chip8.load_state(&state).unwrap();
+    assert_eq!(chip8.frame, frame);
+    assert_eq!(chip8.bus.display.hash(), hash);
+}
+
+#[test]
+fn bad_magic_is_rejected() {
+    let mut chip8 = pong(10);
+    let mut state = chip8.save_state();
+    state[0] = b'X';
+    assert_eq!(chip8.load_state(&state), Err(StateError::BadMagic));
+    assert_eq!(chip8.load_state(b"C8"), Err(StateError::BadMagic));
+}
+
+#[test]
+fn other_versions_are_rejected() {
+    let mut chip8 = pong(10);
+    let mut state = chip8.save_state();
+    state[4] = VERSION + 1;
+    assert_eq!(
+        chip8.load_state(&state),
+        Err(StateError::UnsupportedVersion(VERSION + 1))
+    );
+}
+
+#[test]
+fn truncated_states_are_rejected() {
+    let mut chip8 = pong(10);
+    let state = chip8.save_state();
+    let before = chip8.save_state();
+    for len in [5, 20, state.len() / 2, state.len() - 1].iter() {
+        assert_eq!(chip8.load_state(&state[..*len]), Err(StateError::Truncated));
+    }
+    // A failed load leaves the machine untouched
+    assert_eq!(chip8.save_state(), before);
+}
+
+#[test]
+fn trailing_data_is_rejected() {
+    let mut chip8 = pong(10);
+    let mut state = chip8.save_state();
+    state.push(0);
+    assert_eq!(chip8.load_state(&state), Err(StateError::Invalid("length")));
+}